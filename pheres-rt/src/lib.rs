@@ -1,17 +1,32 @@
-use std::mem::forget;
-use std::{os::raw::c_char, ptr::NonNull};
+use std::{mem::forget, ptr};
 
 #[repr(C)]
 pub struct Context {
-    _unused: (),
+    next_variable: u64,
 }
 
 #[no_mangle]
 pub extern "C" fn pheres_context_new() -> Context {
-    println!("hello world");
-    Context { _unused: () }
+    Context { next_variable: 0 }
 }
 
+/// Returns a variable that is distinct from all variables previously
+/// allocated in the same context.
+#[no_mangle]
+pub extern "C" fn pheres_context_new_variable(ctx: &mut Context) -> RawValue {
+    RawValue::Variable(ctx.next_variable())
+}
+
+impl Context {
+    fn next_variable(&mut self) -> u64 {
+        let id = self.next_variable;
+        self.next_variable += 1;
+        id
+    }
+}
+
+/// New variants are added at the end, so that the tags of existing ones
+/// stay the same for C callers.
 #[repr(C)]
 pub enum RawValue {
     Integer(i64),
@@ -30,6 +45,7 @@ pub enum RawValue {
         annotations_len: usize,
         annotations_capacity: usize,
     },
+    Variable(u64),
 }
 
 #[no_mangle]
@@ -80,3 +96,248 @@ pub extern "C" fn pheres_value_push_arg(term: &mut RawValue, arg: RawValue) {
         _ => unreachable!("pheres_value_push_arg called on non-term")
     }
 }
+
+/// An owned value for Rust code, converted to and from [`RawValue`] only at
+/// the FFI boundary. Strings, functors and argument vectors are freed when it
+/// is dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedValue {
+    Integer(i64),
+    Float(f64),
+    Variable(u64),
+    String(String),
+    Term {
+        functor: String,
+        args: Vec<OwnedValue>,
+        annotations: Vec<OwnedValue>,
+    },
+}
+
+impl OwnedValue {
+    pub fn atom(functor: impl Into<String>) -> OwnedValue {
+        OwnedValue::term(functor, Vec::new())
+    }
+
+    /// A compound term without annotations.
+    pub fn term(functor: impl Into<String>, args: Vec<OwnedValue>) -> OwnedValue {
+        OwnedValue::Term {
+            functor: functor.into(),
+            args,
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn string(s: impl Into<String>) -> OwnedValue {
+        OwnedValue::String(s.into())
+    }
+
+    /// A variable that is distinct from all variables previously allocated
+    /// in the same context.
+    pub fn variable(ctx: &mut Context) -> OwnedValue {
+        OwnedValue::Variable(ctx.next_variable())
+    }
+
+    /// Adds an annotation to a term. Other values are returned unchanged.
+    pub fn with_annotation(mut self, annotation: OwnedValue) -> OwnedValue {
+        if let OwnedValue::Term { annotations, .. } = &mut self {
+            annotations.push(annotation);
+        }
+        self
+    }
+
+    /// Hands all allocations over to the returned raw value. They are leaked
+    /// unless the raw value is passed back to [`OwnedValue::from_raw`].
+    pub fn into_raw(self) -> RawValue {
+        match self {
+            OwnedValue::Integer(n) => RawValue::Integer(n),
+            OwnedValue::Float(f) => RawValue::Float(f),
+            OwnedValue::Variable(id) => RawValue::Variable(id),
+            OwnedValue::String(s) => {
+                let (ptr, len) = str_into_raw(s);
+                RawValue::String { ptr, len }
+            }
+            OwnedValue::Term {
+                functor,
+                args,
+                annotations,
+            } => {
+                let (functor_ptr, functor_len) = str_into_raw(functor);
+                let (args_ptr, args_len, args_capacity) = vec_into_raw(args);
+                let (annotations_ptr, annotations_len, annotations_capacity) =
+                    vec_into_raw(annotations);
+                RawValue::Term {
+                    functor_ptr,
+                    functor_len,
+                    args_ptr,
+                    args_len,
+                    args_capacity,
+                    annotations_ptr,
+                    annotations_len,
+                    annotations_capacity,
+                }
+            }
+        }
+    }
+
+    /// Takes back ownership of a value.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must have been returned by [`OwnedValue::into_raw`], with any
+    /// changes made through `pheres_value_push_arg` only, and must not be
+    /// used afterwards. Values from the borrowing constructors like
+    /// `pheres_value_new_string` do not own their strings.
+    pub unsafe fn from_raw(raw: RawValue) -> OwnedValue {
+        match raw {
+            RawValue::Integer(n) => OwnedValue::Integer(n),
+            RawValue::Float(f) => OwnedValue::Float(f),
+            RawValue::Variable(id) => OwnedValue::Variable(id),
+            RawValue::String { ptr, len } => OwnedValue::String(str_from_raw(ptr, len)),
+            RawValue::Term {
+                functor_ptr,
+                functor_len,
+                args_ptr,
+                args_len,
+                args_capacity,
+                annotations_ptr,
+                annotations_len,
+                annotations_capacity,
+            } => OwnedValue::Term {
+                functor: str_from_raw(functor_ptr, functor_len),
+                args: vec_from_raw(args_ptr, args_len, args_capacity),
+                annotations: vec_from_raw(annotations_ptr, annotations_len, annotations_capacity),
+            },
+        }
+    }
+}
+
+fn str_into_raw(s: String) -> (*const u8, usize) {
+    let s = s.into_boxed_str();
+    let len = s.len();
+    (Box::into_raw(s) as *const u8, len)
+}
+
+unsafe fn str_from_raw(ptr: *const u8, len: usize) -> String {
+    Box::from_raw(ptr::slice_from_raw_parts_mut(ptr as *mut u8, len) as *mut str).into_string()
+}
+
+fn vec_into_raw(values: Vec<OwnedValue>) -> (*mut RawValue, usize, usize) {
+    let mut raw: Vec<RawValue> = values.into_iter().map(OwnedValue::into_raw).collect();
+    let parts = (raw.as_mut_ptr(), raw.len(), raw.capacity());
+    forget(raw);
+    parts
+}
+
+unsafe fn vec_from_raw(ptr: *mut RawValue, len: usize, capacity: usize) -> Vec<OwnedValue> {
+    Vec::from_raw_parts(ptr, len, capacity)
+        .into_iter()
+        .map(|raw| OwnedValue::from_raw(raw))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owned_value_round_trip() {
+        let value = OwnedValue::Term {
+            functor: "likes".to_owned(),
+            args: vec![
+                OwnedValue::atom("alice"),
+                OwnedValue::Variable(3),
+                OwnedValue::Term {
+                    functor: "f".to_owned(),
+                    args: vec![OwnedValue::Integer(1), OwnedValue::Float(0.5)],
+                    annotations: vec![OwnedValue::String("nested".to_owned())],
+                },
+                OwnedValue::String(String::new()),
+            ],
+            annotations: vec![OwnedValue::Term {
+                functor: "source".to_owned(),
+                args: vec![OwnedValue::atom("self")],
+                annotations: Vec::new(),
+            }],
+        };
+
+        let raw = value.clone().into_raw();
+        assert!(matches!(raw, RawValue::Term { args_len: 4, annotations_len: 1, .. }));
+        assert_eq!(unsafe { OwnedValue::from_raw(raw) }, value);
+    }
+
+    #[test]
+    fn test_owned_value_constructors() {
+        let mut ctx = pheres_context_new();
+        let x = OwnedValue::variable(&mut ctx);
+        let value = OwnedValue::term(
+            "likes",
+            vec![
+                OwnedValue::atom("alice"),
+                x.clone(),
+                OwnedValue::term("f", vec![x, OwnedValue::string("y")]),
+            ],
+        )
+        .with_annotation(OwnedValue::term("source", vec![OwnedValue::atom("self")]));
+        assert_eq!(
+            value,
+            OwnedValue::Term {
+                functor: "likes".to_owned(),
+                args: vec![
+                    OwnedValue::atom("alice"),
+                    OwnedValue::Variable(0),
+                    OwnedValue::Term {
+                        functor: "f".to_owned(),
+                        args: vec![OwnedValue::Variable(0), OwnedValue::String("y".to_owned())],
+                        annotations: Vec::new(),
+                    },
+                ],
+                annotations: vec![OwnedValue::term("source", vec![OwnedValue::atom("self")])],
+            }
+        );
+        assert_eq!(OwnedValue::variable(&mut ctx), OwnedValue::Variable(1));
+        assert_eq!(unsafe { OwnedValue::from_raw(value.clone().into_raw()) }, value);
+    }
+
+    #[test]
+    fn test_owned_value_push_arg() {
+        let mut raw = OwnedValue::atom("f").into_raw();
+        pheres_value_push_arg(&mut raw, OwnedValue::Integer(1).into_raw());
+        pheres_value_push_arg(&mut raw, OwnedValue::String("x".to_owned()).into_raw());
+        assert_eq!(
+            unsafe { OwnedValue::from_raw(raw) },
+            OwnedValue::Term {
+                functor: "f".to_owned(),
+                args: vec![OwnedValue::Integer(1), OwnedValue::String("x".to_owned())],
+                annotations: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_new_variable() {
+        let mut ctx = pheres_context_new();
+        let ids: Vec<u64> = (0..4)
+            .map(|_| match pheres_context_new_variable(&mut ctx) {
+                RawValue::Variable(id) => id,
+                _ => panic!("expected variable"),
+            })
+            .collect();
+        assert_eq!(ids, [0, 1, 2, 3]);
+
+        let mut other = pheres_context_new();
+        assert!(matches!(
+            pheres_context_new_variable(&mut other),
+            RawValue::Variable(0)
+        ));
+    }
+
+    #[test]
+    fn test_raw_value_tags() {
+        // The C header exposes the tags in declaration order.
+        let tag = |value: &RawValue| unsafe { *(value as *const RawValue as *const u32) };
+        assert_eq!(tag(&RawValue::Integer(1)), 0);
+        assert_eq!(tag(&RawValue::Float(1.0)), 1);
+        assert_eq!(tag(&RawValue::String { ptr: ptr::null(), len: 0 }), 2);
+        assert_eq!(tag(&RawValue::Variable(0)), 4);
+    }
+}
@@ -0,0 +1,71 @@
+//! Internal actions like `.sort` and `.nth`, as functions from their input
+//! arguments to the value that would be unified with their output argument.
+
+use std::cmp::Ordering;
+
+use crate::{
+    eval::EvalError,
+    runtime::{List, Value},
+};
+
+fn elements(list: &Value) -> Result<Vec<&Value>, EvalError> {
+    match list {
+        Value::List(list) => match list.last_tail() {
+            Some(Value::Variable(id)) => Err(EvalError::Unbound(*id)),
+            Some(_) => Err(EvalError::TypeError),
+            None => Ok(list.iter().collect()),
+        },
+        Value::Variable(id) => Err(EvalError::Unbound(*id)),
+        _ => Err(EvalError::TypeError),
+    }
+}
+
+/// `.sort(List, Sorted)`: sorts by the standard order of terms and removes
+/// duplicates.
+pub fn sort(list: &Value) -> Result<Value, EvalError> {
+    let mut elements = elements(list)?;
+    elements.sort_by(|a, b| a.standard_cmp(b));
+    elements.dedup_by(|a, b| a.standard_cmp(b) == Ordering::Equal);
+    Ok(Value::List(Box::new(List::new(
+        elements.into_iter().cloned().collect(),
+        List::Empty,
+    ))))
+}
+
+/// `.nth(Index, List, Element)`: the element at the zero-based index, or
+/// `None` if the index is out of range.
+pub fn nth(index: &Value, list: &Value) -> Result<Option<Value>, EvalError> {
+    let index = match *index {
+        Value::Integer(index) => index,
+        Value::Variable(id) => return Err(EvalError::Unbound(id)),
+        _ => return Err(EvalError::TypeError),
+    };
+    let elements = elements(list)?;
+    Ok(usize::try_from(index)
+        .ok()
+        .and_then(|index| elements.get(index))
+        .map(|element| (*element).clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(
+            sort(&Value::parse("[c, a, b, a]").unwrap()),
+            Ok(Value::parse("[a, b, c]").unwrap())
+        );
+        assert_eq!(sort(&Value::atom("a")), Err(EvalError::TypeError));
+    }
+
+    #[test]
+    fn test_nth() {
+        let list = Value::parse("[a, b, c]").unwrap();
+        assert_eq!(nth(&Value::Integer(1), &list), Ok(Some(Value::atom("b"))));
+        assert_eq!(nth(&Value::Integer(3), &list), Ok(None));
+        assert_eq!(nth(&Value::Integer(-1), &list), Ok(None));
+        assert_eq!(nth(&Value::atom("a"), &list), Err(EvalError::TypeError));
+    }
+}
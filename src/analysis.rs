@@ -0,0 +1,771 @@
+use std::collections::{HashMap, HashSet};
+
+use rowan::{NodeOrToken, TextRange};
+
+use crate::{
+    ast::{AstNode, Body, Comparison, Formula, Literal, PlanContext, Statement, Term},
+    error::Severity,
+    eval::{eval_arithmetic, eval_condition},
+    lower::lower_term,
+    runtime::Interner,
+    syntax::{trimmed_range, ComparisonOperator, FormulaType, SyntaxKind, SyntaxNode, SyntaxToken},
+};
+
+#[derive(Debug)]
+pub struct Warning {
+    pub message: String,
+    pub range: TextRange,
+    pub severity: Severity,
+}
+
+type Signature = (String, usize);
+
+fn literal_signature(literal: &SyntaxNode) -> Option<Signature> {
+    Literal::cast(literal.clone())?.signature()
+}
+
+/// Collects the [`Literal::signature`] of every literal in the file,
+/// including nested literals in arguments, annotations, contexts and bodies.
+pub fn collect_signatures(root: &SyntaxNode) -> HashSet<(String, usize)> {
+    root.descendants()
+        .filter(|node| node.kind() == SyntaxKind::Literal)
+        .filter_map(|literal| literal_signature(&literal))
+        .collect()
+}
+
+/// Collects the literals that may be resolved first when proving a term.
+fn leftmost_goals(node: &SyntaxNode, goals: &mut Vec<SyntaxNode>) {
+    match node.kind() {
+        SyntaxKind::Literal => goals.push(node.clone()),
+        SyntaxKind::Conjunction | SyntaxKind::Negation => {
+            if let Some(first) = node.children().next() {
+                leftmost_goals(&first, goals);
+            }
+        }
+        SyntaxKind::Disjunction => {
+            for child in node.children() {
+                leftmost_goals(&child, goals);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Warns about rules that call themselves, directly or through one other
+/// rule, before anything else in their body is proven. The resolver does not
+/// do tabling, so these can loop forever. This is a heuristic, not a
+/// termination analysis.
+pub fn check_left_recursion(root: &SyntaxNode) -> Vec<Warning> {
+    let mut calls: HashMap<Signature, Vec<(Signature, TextRange)>> = HashMap::new();
+
+    for rule in root
+        .children()
+        .filter(|node| node.kind() == SyntaxKind::Rule)
+    {
+        let mut children = rule.children();
+        let head = match children.next() {
+            Some(head) if head.kind() == SyntaxKind::Literal => head,
+            _ => continue,
+        };
+        let head_signature = match literal_signature(&head) {
+            Some(signature) => signature,
+            None => continue,
+        };
+
+        let mut goals = Vec::new();
+        if let Some(body) = children.next() {
+            leftmost_goals(&body, &mut goals);
+        }
+
+        let callees = calls.entry(head_signature).or_default();
+        for goal in goals {
+            if let Some(signature) = literal_signature(&goal) {
+                callees.push((signature, head.text_range()));
+            }
+        }
+    }
+
+    let mut warnings = Vec::new();
+
+    for (caller, callees) in &calls {
+        for (callee, range) in callees {
+            if callee == caller {
+                warnings.push(Warning {
+                    message: format!("rule for {}/{} is left-recursive", caller.0, caller.1),
+                    range: *range,
+                    severity: Severity::Warning,
+                });
+            } else if calls
+                .get(callee)
+                .is_some_and(|back| back.iter().any(|(c, _)| c == caller))
+            {
+                warnings.push(Warning {
+                    message: format!(
+                        "rule for {}/{} is mutually left-recursive with {}/{}",
+                        caller.0, caller.1, callee.0, callee.1
+                    ),
+                    range: *range,
+                    severity: Severity::Warning,
+                });
+            }
+        }
+    }
+
+    warnings.sort_by_key(|warning| warning.range.start());
+    warnings
+}
+
+/// Collects the comparisons that are evaluated as conditions of a context or
+/// rule body, as opposed to comparisons passed around as data.
+fn conditions(node: &SyntaxNode, out: &mut Vec<Comparison>) {
+    match node.kind() {
+        SyntaxKind::Comparison => out.extend(Comparison::cast(node.clone())),
+        SyntaxKind::Conjunction | SyntaxKind::Disjunction | SyntaxKind::Negation => {
+            for child in node.children() {
+                conditions(&child, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn is_ground(term: &Term) -> bool {
+    match term.syntax() {
+        NodeOrToken::Node(node) => !node
+            .descendants_with_tokens()
+            .any(|element| matches!(element.kind(), SyntaxKind::Variable | SyntaxKind::Wildcard)),
+        NodeOrToken::Token(token) => {
+            !matches!(token.kind(), SyntaxKind::Variable | SyntaxKind::Wildcard)
+        }
+    }
+}
+
+/// Warns about `=` between two ground terms in plan contexts and rule
+/// bodies. Such a unification cannot bind anything, so the author most
+/// likely meant the equality test `==`. This is a heuristic.
+pub fn check_suspicious_unification(root: &SyntaxNode) -> Vec<Warning> {
+    let mut comparisons = Vec::new();
+
+    for node in root.descendants() {
+        match node.kind() {
+            SyntaxKind::PlanContext => {
+                for child in node.children() {
+                    conditions(&child, &mut comparisons);
+                }
+            }
+            SyntaxKind::Rule => {
+                if let Some(body) = node.children().nth(1) {
+                    conditions(&body, &mut comparisons);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    comparisons
+        .into_iter()
+        .filter(|comparison| comparison.op() == Some(ComparisonOperator::Eq))
+        .filter(|comparison| match (comparison.lhs(), comparison.rhs()) {
+            (Some(lhs), Some(rhs)) => is_ground(&lhs) && is_ground(&rhs),
+            _ => false,
+        })
+        .filter_map(|comparison| {
+            let op = comparison
+                .syntax()
+                .children_with_tokens()
+                .find(|element| element.kind() == SyntaxKind::Eq)?;
+            Some(Warning {
+                message: "'=' between ground terms cannot bind anything, did you mean '=='?"
+                    .to_owned(),
+                range: op.text_range(),
+                severity: Severity::Warning,
+            })
+        })
+        .collect()
+}
+
+fn variables(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> {
+    node.descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| token.kind() == SyntaxKind::Variable)
+}
+
+/// Splits a condition into its conjuncts, left to right.
+fn conjuncts(node: &SyntaxNode, out: &mut Vec<SyntaxNode>) {
+    if node.kind() == SyntaxKind::Conjunction {
+        for child in node.children() {
+            conjuncts(&child, out);
+        }
+    } else {
+        out.push(node.clone());
+    }
+}
+
+fn check_negations(
+    bound: &mut HashSet<String>,
+    condition: &SyntaxNode,
+    warnings: &mut Vec<Warning>,
+) {
+    let mut parts = Vec::new();
+    conjuncts(condition, &mut parts);
+    for part in parts {
+        if part.kind() == SyntaxKind::Negation {
+            let mut reported = HashSet::new();
+            for variable in variables(&part) {
+                if !bound.contains(variable.text()) && reported.insert(variable.text().to_owned()) {
+                    warnings.push(Warning {
+                        message: format!(
+                            "{} is unbound in a negated goal, so the negation may flounder",
+                            variable.text()
+                        ),
+                        range: variable.text_range(),
+                        severity: Severity::Warning,
+                    });
+                }
+            }
+        } else {
+            bound.extend(variables(&part).map(|token| token.text().to_owned()));
+        }
+    }
+}
+
+/// Warns about variables in `not` goals of contexts and rule bodies that are
+/// not bound by the trigger, the rule head or an earlier conjunct. Negation
+/// as failure cannot bind them, so such a goal usually does not mean what
+/// it says. Anonymous variables are fine.
+pub fn check_floundering(root: &SyntaxNode) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for node in root.descendants() {
+        match node.kind() {
+            SyntaxKind::Plan => {
+                let mut bound = HashSet::new();
+                if let Some(trigger) = node
+                    .children()
+                    .find(|child| child.kind() == SyntaxKind::Literal)
+                {
+                    bound.extend(variables(&trigger).map(|token| token.text().to_owned()));
+                }
+                if let Some(condition) = node
+                    .children()
+                    .find(|child| child.kind() == SyntaxKind::PlanContext)
+                    .and_then(|context| context.first_child())
+                {
+                    check_negations(&mut bound, &condition, &mut warnings);
+                }
+            }
+            SyntaxKind::Rule => {
+                let mut children = node.children();
+                let mut bound = HashSet::new();
+                if let Some(head) = children.next() {
+                    bound.extend(variables(&head).map(|token| token.text().to_owned()));
+                }
+                if let Some(body) = children.next() {
+                    check_negations(&mut bound, &body, &mut warnings);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    warnings
+}
+
+/// Non-trivia tokens of a plan without its label and annotations, with
+/// variables numbered by first occurrence, so that plans that only differ in
+/// layout, comments or variable names have the same key.
+fn plan_key(plan: &SyntaxNode) -> Vec<(SyntaxKind, String)> {
+    let mut variables: HashMap<String, usize> = HashMap::new();
+    plan.children_with_tokens()
+        .filter(|element| element.kind() != SyntaxKind::PlanAnnotation)
+        .flat_map(|element| match element {
+            NodeOrToken::Node(node) => node
+                .descendants_with_tokens()
+                .filter_map(|element| element.into_token())
+                .collect(),
+            NodeOrToken::Token(token) => vec![token],
+        })
+        .filter(|token| !token.kind().is_trivia())
+        .map(|token| {
+            let text = match token.kind() {
+                SyntaxKind::Variable => {
+                    let next = variables.len();
+                    variables
+                        .entry(token.text().to_owned())
+                        .or_insert(next)
+                        .to_string()
+                }
+                _ => token.text().to_owned(),
+            };
+            (token.kind(), text)
+        })
+        .collect()
+}
+
+/// Warns about plans with the same trigger, context and body as an earlier
+/// plan. Labels, annotations, layout and variable names are ignored, and the
+/// warning starts at the trigger, after any label.
+pub fn check_duplicate_plans(root: &SyntaxNode) -> Vec<Warning> {
+    let mut seen = HashSet::new();
+    root.descendants()
+        .filter(|node| node.kind() == SyntaxKind::Plan)
+        .filter(|plan| !seen.insert(plan_key(plan)))
+        .map(|plan| {
+            let range = trimmed_range(&plan);
+            let start = plan
+                .children_with_tokens()
+                .find(|element| {
+                    element.kind() != SyntaxKind::PlanAnnotation && !element.kind().is_trivia()
+                })
+                .map_or(range.start(), |element| element.text_range().start());
+            Warning {
+                message: "plan is identical to an earlier plan".to_owned(),
+                range: TextRange::new(start, range.end()),
+                severity: Severity::Warning,
+            }
+        })
+        .collect()
+}
+
+/// Warns about rules and beliefs whose head is not a single literal, like
+/// `p + 1 :- q.`. The parser reads the head as a literal and reports a
+/// missing `.` at the first token after it; this explains what the head
+/// should have been.
+pub fn check_heads(root: &SyntaxNode) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for node in root.descendants() {
+        if !matches!(node.kind(), SyntaxKind::Belief | SyntaxKind::Rule) {
+            continue;
+        }
+
+        let mut elements = node
+            .children_with_tokens()
+            .filter(|element| !element.kind().is_trivia());
+        let Some(head) = elements.next() else {
+            continue;
+        };
+        let start = head.text_range().start();
+        let is_literal = head
+            .as_node()
+            .and_then(|head| Literal::cast(head.clone()))
+            .is_some_and(|literal| literal.functor().is_some());
+
+        let end = match elements.next() {
+            Some(NodeOrToken::Node(error)) if error.kind() == SyntaxKind::Error => {
+                // The rest of the head up to `:-`, or the whole item.
+                let tokens = error
+                    .descendants_with_tokens()
+                    .filter_map(|element| element.into_token())
+                    .filter(|token| !token.kind().is_trivia())
+                    .take_while(|token| {
+                        !matches!(token.kind(), SyntaxKind::Define | SyntaxKind::Dot)
+                    });
+                match tokens.last() {
+                    Some(last) => last.text_range().end(),
+                    None if is_literal => continue,
+                    None => trimmed_range(&error).end(),
+                }
+            }
+            _ if is_literal => continue,
+            _ => trimmed_range(&node).end(),
+        };
+
+        warnings.push(Warning {
+            message: "head of a rule or belief must be a single literal".to_owned(),
+            range: TextRange::new(start, end),
+            severity: Severity::Warning,
+        });
+    }
+
+    warnings
+}
+
+/// Whether executing the formula always fails: the `.fail` action or the
+/// condition `false`.
+fn always_fails(formula: &Formula) -> bool {
+    if formula.kind() != FormulaType::Term {
+        return false;
+    }
+    match formula.term() {
+        Some(Term::Literal(literal)) => literal
+            .functor()
+            .is_some_and(|functor| functor.text() == ".fail"),
+        Some(Term::Primitive(token)) => token.kind() == SyntaxKind::False,
+        _ => false,
+    }
+}
+
+/// Warns about statements in a plan body after a formula that always fails,
+/// since the intention never gets to them. Best effort: only `.fail` and `false`
+/// are recognized.
+pub fn check_unreachable_formulas(root: &SyntaxNode) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for body in root.descendants().filter_map(Body::cast) {
+        let mut statements = body.statements().skip_while(
+            |statement| !matches!(statement, Statement::Formula(formula) if always_fails(formula)),
+        );
+        if statements.next().is_none() {
+            continue;
+        }
+        let rest: Vec<_> = statements
+            .map(|statement| trimmed_range(statement.syntax()))
+            .collect();
+        if let (Some(first), Some(last)) = (rest.first(), rest.last()) {
+            warnings.push(Warning {
+                message: "unreachable after a formula that always fails".to_owned(),
+                range: first.cover(*last),
+                severity: Severity::Warning,
+            });
+        }
+    }
+
+    warnings
+}
+
+/// Values a variable may take according to numeric comparisons. Bounds are
+/// `(value, inclusive)`.
+#[derive(Debug, Default)]
+struct Interval {
+    lower: Option<(f64, bool)>,
+    upper: Option<(f64, bool)>,
+}
+
+impl Interval {
+    fn restrict(&mut self, op: ComparisonOperator, bound: f64) {
+        match op {
+            ComparisonOperator::Gt => self.raise(bound, false),
+            ComparisonOperator::GtEq => self.raise(bound, true),
+            ComparisonOperator::Lt => self.lower_to(bound, false),
+            ComparisonOperator::LtEq => self.lower_to(bound, true),
+            ComparisonOperator::Equal | ComparisonOperator::ArithEqual | ComparisonOperator::Eq => {
+                self.raise(bound, true);
+                self.lower_to(bound, true);
+            }
+            ComparisonOperator::NotEqual
+            | ComparisonOperator::ArithNotEqual
+            | ComparisonOperator::Decompose => (),
+        }
+    }
+
+    fn raise(&mut self, bound: f64, inclusive: bool) {
+        if self.lower.is_none_or(|(lower, lower_inclusive)| {
+            bound > lower || (bound == lower && !inclusive && lower_inclusive)
+        }) {
+            self.lower = Some((bound, inclusive));
+        }
+    }
+
+    fn lower_to(&mut self, bound: f64, inclusive: bool) {
+        if self.upper.is_none_or(|(upper, upper_inclusive)| {
+            bound < upper || (bound == upper && !inclusive && upper_inclusive)
+        }) {
+            self.upper = Some((bound, inclusive));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (self.lower, self.upper) {
+            (Some((lower, lower_inclusive)), Some((upper, upper_inclusive))) => {
+                lower > upper || (lower == upper && !(lower_inclusive && upper_inclusive))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The value of a ground arithmetic expression.
+fn number(term: &Term) -> Option<f64> {
+    if !is_ground(term) {
+        return None;
+    }
+    let value = lower_term(term, &mut Interner::default()).ok()?;
+    eval_arithmetic(&value).ok()?.as_f64()
+}
+
+/// A comparison between a named variable and a number, normalized so that
+/// the variable is on the left.
+fn variable_bound(comparison: &Comparison) -> Option<(String, ComparisonOperator, f64)> {
+    let op = comparison.op()?;
+    match (comparison.lhs()?, comparison.rhs()?) {
+        (Term::Primitive(variable), other) if variable.kind() == SyntaxKind::Variable => {
+            Some((variable.text().to_owned(), op, number(&other)?))
+        }
+        (other, Term::Primitive(variable)) if variable.kind() == SyntaxKind::Variable => {
+            let flipped = match op {
+                ComparisonOperator::Lt => ComparisonOperator::Gt,
+                ComparisonOperator::Gt => ComparisonOperator::Lt,
+                ComparisonOperator::LtEq => ComparisonOperator::GtEq,
+                ComparisonOperator::GtEq => ComparisonOperator::LtEq,
+                op => op,
+            };
+            Some((variable.text().to_owned(), flipped, number(&other)?))
+        }
+        _ => None,
+    }
+}
+
+fn is_unsatisfiable(context: &PlanContext) -> bool {
+    let parts = context.term().map_or_else(Vec::new, Term::conjuncts);
+
+    let mut intervals: HashMap<String, Interval> = HashMap::new();
+    for part in parts {
+        if is_ground(&part) {
+            let value = lower_term(&part, &mut Interner::default());
+            if value.is_ok_and(|value| eval_condition(&value) == Ok(false)) {
+                return true;
+            }
+        } else if let Term::Comparison(comparison) = &part {
+            if let Some((variable, op, bound)) = variable_bound(comparison) {
+                let interval = intervals.entry(variable).or_default();
+                interval.restrict(op, bound);
+                if interval.is_empty() {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Warns about plan contexts that can never hold, like `X > 5 & X < 3` or
+/// `true & false`. Only conjunctions of ground conditions and of numeric
+/// comparisons between a variable and a ground expression are considered.
+pub fn check_contradictory_context(root: &SyntaxNode) -> Vec<Warning> {
+    root.descendants()
+        .filter_map(PlanContext::cast)
+        .filter(is_unsatisfiable)
+        .map(|context| Warning {
+            message: "context can never hold, so the plan is never applicable".to_owned(),
+            range: trimmed_range(context.syntax()),
+            severity: Severity::Warning,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse, syntax::LexedStr};
+
+    fn check(source: &str) -> Vec<String> {
+        let lexed = LexedStr::new(source);
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        check_left_recursion(&parsed.syntax())
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_signatures() {
+        let lexed = LexedStr::new(
+            "at(home).\n\
+             near(X, Y) :- at(X) & adjacent(X, Y).\n\
+             !start.\n\
+             +!go(Place)[source(self)] : near(Place, _) <- move(Place); .print(done).\n",
+        );
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let signatures = collect_signatures(&parsed.syntax());
+
+        let mut signatures: Vec<_> = signatures.into_iter().collect();
+        signatures.sort();
+        assert_eq!(
+            signatures,
+            [
+                (".print".to_owned(), 1),
+                ("adjacent".to_owned(), 2),
+                ("at".to_owned(), 1),
+                ("done".to_owned(), 0),
+                ("go".to_owned(), 1),
+                ("home".to_owned(), 0),
+                ("move".to_owned(), 1),
+                ("near".to_owned(), 2),
+                ("self".to_owned(), 0),
+                ("source".to_owned(), 1),
+                ("start".to_owned(), 0),
+            ]
+        );
+
+        let lexed = LexedStr::new("p(a, ).\nq :- p(a, ).\n");
+        let parsed = parse(&lexed);
+        assert_eq!(parsed.errors.len(), 2);
+        let signatures = collect_signatures(&parsed.syntax());
+        assert!(signatures.contains(&("p".to_owned(), 1)));
+        assert!(!signatures.contains(&("p".to_owned(), 2)));
+    }
+
+    #[test]
+    fn test_suspicious_unification() {
+        let warnings = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            check_suspicious_unification(&parsed.syntax())
+                .into_iter()
+                .map(|warning| source[warning.range].to_owned() + " " + &warning.message)
+                .collect()
+        };
+
+        assert!(warnings("+!go : X = 5 <- move(X).").is_empty());
+        assert!(warnings("+!go : pos(P) & P = f(_) <- move(P).").is_empty());
+        assert!(warnings("+!go : foo == bar <- move.").is_empty());
+        assert!(warnings("+!go : ok <- move(foo = bar).").is_empty());
+        assert_eq!(
+            warnings("+!go : foo = bar <- move."),
+            ["= '=' between ground terms cannot bind anything, did you mean '=='?"]
+        );
+        assert_eq!(warnings("p(X) :- q(X) & not (f(1) = f(2)).").len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_plans() {
+        let warnings = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            check_duplicate_plans(&parsed.syntax())
+                .into_iter()
+                .map(|warning| source[warning.range].to_owned())
+                .collect()
+        };
+
+        assert_eq!(
+            warnings(
+                "+!go(X) : at(X) <- move(X).\n\
+                 @second\n\
+                 +!go(Y) : at(Y) // same plan\n\
+                 \x20   <- move(Y).\n"
+            ),
+            ["+!go(Y) : at(Y) // same plan\n    <- move(Y)."]
+        );
+        assert_eq!(
+            warnings("@a[atomic] +b <- x.\n@b[atomic] /* again */ +b <- x."),
+            ["+b <- x."]
+        );
+        assert!(warnings("+!go(X) : at(X) <- move(X).\n+!go(X) : at(X) <- stay(X).").is_empty());
+        assert!(warnings("+!go(X, Y) <- move(X, Y).\n+!go(X, Y) <- move(Y, X).").is_empty());
+    }
+
+    #[test]
+    fn test_heads() {
+        let warnings = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            check_heads(&parse(&lexed).syntax())
+                .into_iter()
+                .map(|warning| source[warning.range].to_owned())
+                .collect()
+        };
+
+        assert!(warnings("p(X)[source(self)] :- q(X).\n~at(home).\nat(work).").is_empty());
+        assert_eq!(warnings("p + 1 :- q."), ["p + 1"]);
+        assert_eq!(warnings("count(N) * 2."), ["count(N) * 2"]);
+        assert!(warnings("p :- q r.").is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_formulas() {
+        let warnings = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            check_unreachable_formulas(&parsed.syntax())
+                .into_iter()
+                .map(|warning| source[warning.range].to_owned())
+                .collect()
+        };
+
+        assert_eq!(warnings("+!go <- .fail; +done."), ["+done"]);
+        assert_eq!(
+            warnings("+!go <- act; false; !a; .print(x)."),
+            ["!a; .print(x)"]
+        );
+        assert!(warnings("+!go <- act; +done; .fail.").is_empty());
+        assert!(warnings("+!go <- .failed; +done.").is_empty());
+        assert_eq!(
+            warnings("+!g <- .fail; while (x) { a }."),
+            ["while (x) { a }"]
+        );
+        assert_eq!(
+            warnings("+!h <- .fail; while (x) { a }; b."),
+            ["while (x) { a }; b"]
+        );
+        assert_eq!(warnings("+!i <- if (x) { .fail; a }; b."), ["a"]);
+    }
+
+    #[test]
+    fn test_contradictory_context() {
+        let warned = |source: &str| -> bool {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            let warnings = check_contradictory_context(&parsed.syntax());
+            assert!(warnings.len() <= 1);
+            !warnings.is_empty()
+        };
+
+        assert!(warned("+!go : X > 5 & X < 3 <- x."));
+        assert!(!warned("+!go : X > 5 & X < 10 <- x."));
+        assert!(warned("+!go : true & false <- x."));
+        assert!(!warned("+!go : true & at(home) <- x."));
+        assert!(warned("+!go : at(X) & X >= 2 * 3 & 6 > X <- x."));
+        assert!(!warned("+!go : X >= 6 & X <= 6 <- x."));
+        assert!(warned("+!go : X == 4 & X > 4 <- x."));
+        assert!(!warned("+!go : X > 5 & Y < 3 <- x."));
+        assert!(!warned("+!go : X > 5 | X < 3 <- x."));
+        assert!(warned("+!go : 1 > 2 <- x."));
+    }
+
+    #[test]
+    fn test_floundering() {
+        let warnings = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            check_floundering(&parsed.syntax())
+                .into_iter()
+                .map(|warning| warning.message)
+                .collect()
+        };
+
+        assert!(warnings("+!go : not member(d, [a, b, c]) <- x.").is_empty());
+        assert!(warnings("+!go(X) : not at(X) <- x.").is_empty());
+        assert!(warnings("+!go : pos(X) & not at(X) & not seen(_) <- x.").is_empty());
+        assert!(warnings("safe(X) :- cell(X) & not wall(X).").is_empty());
+        assert_eq!(
+            warnings("+!go : not at(X) & pos(X) <- x."),
+            ["X is unbound in a negated goal, so the negation may flounder"]
+        );
+        assert_eq!(
+            warnings("free(X) :- not occupied(X, Y, Y)."),
+            ["Y is unbound in a negated goal, so the negation may flounder"]
+        );
+    }
+
+    #[test]
+    fn test_direct_left_recursion() {
+        assert_eq!(
+            check("p(X) :- p(X).\nq(X) :- r(X) & q(X).\nr(1)."),
+            ["rule for p/1 is left-recursive"]
+        );
+        assert!(check("~p(X) :- p(X).").is_empty());
+        assert_eq!(
+            check("~p(X) :- ~p(X)."),
+            ["rule for ~p/1 is left-recursive"]
+        );
+    }
+
+    #[test]
+    fn test_mutual_left_recursion() {
+        assert_eq!(
+            check("p(X) :- q(X) & r(X).\nq(X) :- p(X).\n"),
+            [
+                "rule for p/1 is mutually left-recursive with q/1",
+                "rule for q/1 is mutually left-recursive with p/1",
+            ]
+        );
+    }
+}
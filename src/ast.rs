@@ -0,0 +1,1166 @@
+use rowan::{NodeOrToken, TextRange, TextSize};
+
+use crate::syntax::{
+    trimmed_range, unescape, AdditiveOperator, ComparisonOperator, FormulaType,
+    MultiplicativeOperator, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, TriggerKind,
+    UnaryOperator,
+};
+
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! ast_node {
+    ($name:ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<$name> {
+                (node.kind() == SyntaxKind::$name).then(|| $name(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(Root);
+ast_node!(Belief);
+ast_node!(Rule);
+ast_node!(InitialGoal);
+ast_node!(Directive);
+ast_node!(Plan);
+ast_node!(PlanGroup);
+ast_node!(PlanAnnotation);
+ast_node!(PlanContext);
+ast_node!(Body);
+ast_node!(Formula);
+ast_node!(WhileLoop);
+ast_node!(ForLoop);
+ast_node!(IfThenElse);
+ast_node!(Literal);
+ast_node!(LiteralTerms);
+ast_node!(LiteralAnnotations);
+ast_node!(List);
+ast_node!(ListTail);
+ast_node!(Disjunction);
+ast_node!(Conjunction);
+ast_node!(Negation);
+ast_node!(Comparison);
+ast_node!(ShiftExpression);
+ast_node!(AdditiveExpression);
+ast_node!(MultiplicativeExpression);
+ast_node!(UnaryExpression);
+ast_node!(Exponentiation);
+ast_node!(Missing);
+
+fn child<N: AstNode>(node: &SyntaxNode) -> Option<N> {
+    node.children().find_map(N::cast)
+}
+
+fn children<N: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = N> {
+    node.children().filter_map(N::cast)
+}
+
+fn terms(node: &SyntaxNode) -> impl Iterator<Item = Term> {
+    node.children_with_tokens().filter_map(Term::cast)
+}
+
+fn token<T>(node: &SyntaxNode, f: impl Fn(SyntaxKind) -> Option<T>) -> Option<T> {
+    node.children_with_tokens()
+        .filter_map(|element| element.into_token())
+        .find_map(|token| f(token.kind()))
+}
+
+/// A top-level item of an agent file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Item {
+    Belief(Belief),
+    Rule(Rule),
+    InitialGoal(InitialGoal),
+    Directive(Directive),
+    Plan(Plan),
+    PlanGroup(PlanGroup),
+}
+
+impl AstNode for Item {
+    fn cast(node: SyntaxNode) -> Option<Item> {
+        Some(match node.kind() {
+            SyntaxKind::Belief => Item::Belief(Belief(node)),
+            SyntaxKind::Rule => Item::Rule(Rule(node)),
+            SyntaxKind::InitialGoal => Item::InitialGoal(InitialGoal(node)),
+            SyntaxKind::Directive => Item::Directive(Directive(node)),
+            SyntaxKind::Plan => Item::Plan(Plan(node)),
+            SyntaxKind::PlanGroup => Item::PlanGroup(PlanGroup(node)),
+            _ => return None,
+        })
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Item::Belief(it) => it.syntax(),
+            Item::Rule(it) => it.syntax(),
+            Item::InitialGoal(it) => it.syntax(),
+            Item::Directive(it) => it.syntax(),
+            Item::Plan(it) => it.syntax(),
+            Item::PlanGroup(it) => it.syntax(),
+        }
+    }
+}
+
+/// A statement of a plan body: a formula or a control-flow block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Statement {
+    Formula(Formula),
+    WhileLoop(WhileLoop),
+    ForLoop(ForLoop),
+    IfThenElse(IfThenElse),
+}
+
+impl AstNode for Statement {
+    fn cast(node: SyntaxNode) -> Option<Statement> {
+        Some(match node.kind() {
+            SyntaxKind::Formula => Statement::Formula(Formula(node)),
+            SyntaxKind::WhileLoop => Statement::WhileLoop(WhileLoop(node)),
+            SyntaxKind::ForLoop => Statement::ForLoop(ForLoop(node)),
+            SyntaxKind::IfThenElse => Statement::IfThenElse(IfThenElse(node)),
+            _ => return None,
+        })
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Statement::Formula(it) => it.syntax(),
+            Statement::WhileLoop(it) => it.syntax(),
+            Statement::ForLoop(it) => it.syntax(),
+            Statement::IfThenElse(it) => it.syntax(),
+        }
+    }
+}
+
+/// Any logical or arithmetic term. Variables, numbers, strings and booleans
+/// are plain tokens in the tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    Literal(Literal),
+    List(List),
+    Disjunction(Disjunction),
+    Conjunction(Conjunction),
+    Negation(Negation),
+    Comparison(Comparison),
+    ShiftExpression(ShiftExpression),
+    AdditiveExpression(AdditiveExpression),
+    MultiplicativeExpression(MultiplicativeExpression),
+    UnaryExpression(UnaryExpression),
+    Exponentiation(Exponentiation),
+    Primitive(SyntaxToken),
+}
+
+impl Term {
+    pub fn cast(element: SyntaxElement) -> Option<Term> {
+        match element {
+            NodeOrToken::Node(node) => Some(match node.kind() {
+                SyntaxKind::Literal => Term::Literal(Literal(node)),
+                SyntaxKind::List => Term::List(List(node)),
+                SyntaxKind::Disjunction => Term::Disjunction(Disjunction(node)),
+                SyntaxKind::Conjunction => Term::Conjunction(Conjunction(node)),
+                SyntaxKind::Negation => Term::Negation(Negation(node)),
+                SyntaxKind::Comparison => Term::Comparison(Comparison(node)),
+                SyntaxKind::ShiftExpression => Term::ShiftExpression(ShiftExpression(node)),
+                SyntaxKind::AdditiveExpression => {
+                    Term::AdditiveExpression(AdditiveExpression(node))
+                }
+                SyntaxKind::MultiplicativeExpression => {
+                    Term::MultiplicativeExpression(MultiplicativeExpression(node))
+                }
+                SyntaxKind::UnaryExpression => Term::UnaryExpression(UnaryExpression(node)),
+                SyntaxKind::Exponentiation => Term::Exponentiation(Exponentiation(node)),
+                _ => return None,
+            }),
+            NodeOrToken::Token(token) => match token.kind() {
+                SyntaxKind::Variable
+                | SyntaxKind::Wildcard
+                | SyntaxKind::Integer
+                | SyntaxKind::Float
+                | SyntaxKind::String
+                | SyntaxKind::True
+                | SyntaxKind::False => Some(Term::Primitive(token)),
+                _ => None,
+            },
+        }
+    }
+
+    /// The operands of `&`, left to right, or just the term itself if it is
+    /// not a conjunction.
+    pub fn conjuncts(self) -> Vec<Term> {
+        match self {
+            Term::Conjunction(conjunction) => conjunction.operands().collect(),
+            term => vec![term],
+        }
+    }
+
+    /// Source range of the term without surrounding trivia.
+    pub fn text_range(&self) -> TextRange {
+        match self.syntax() {
+            NodeOrToken::Node(node) => trimmed_range(&node),
+            NodeOrToken::Token(token) => token.text_range(),
+        }
+    }
+
+    pub fn syntax(&self) -> SyntaxElement {
+        match self {
+            Term::Literal(it) => it.syntax().clone().into(),
+            Term::List(it) => it.syntax().clone().into(),
+            Term::Disjunction(it) => it.syntax().clone().into(),
+            Term::Conjunction(it) => it.syntax().clone().into(),
+            Term::Negation(it) => it.syntax().clone().into(),
+            Term::Comparison(it) => it.syntax().clone().into(),
+            Term::ShiftExpression(it) => it.syntax().clone().into(),
+            Term::AdditiveExpression(it) => it.syntax().clone().into(),
+            Term::MultiplicativeExpression(it) => it.syntax().clone().into(),
+            Term::UnaryExpression(it) => it.syntax().clone().into(),
+            Term::Exponentiation(it) => it.syntax().clone().into(),
+            Term::Primitive(token) => token.clone().into(),
+        }
+    }
+}
+
+/// What was expected where a [`Missing`] node was inserted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// A functor, like the trigger after `+!`.
+    Literal,
+    /// A body formula, like after `<-` or `;`.
+    Formula,
+    /// A plan context, after `:`.
+    Context,
+    /// Any other term, like an argument or an operand.
+    Term,
+}
+
+impl Missing {
+    pub fn expected(&self) -> Expected {
+        match self.0.parent().map(|parent| parent.kind()) {
+            Some(SyntaxKind::Literal) => Expected::Literal,
+            Some(SyntaxKind::Formula) => Expected::Formula,
+            Some(SyntaxKind::PlanContext) => Expected::Context,
+            _ => Expected::Term,
+        }
+    }
+}
+
+impl Root {
+    /// Top-level items in source order. Erroneous input that did not parse
+    /// as an item is skipped.
+    pub fn items(&self) -> impl Iterator<Item = Item> {
+        children(&self.0)
+    }
+
+    /// The construct missing at the cursor `offset`, if only trivia
+    /// separates the cursor from where the parser expected it.
+    pub fn missing_at(&self, offset: TextSize) -> Option<Missing> {
+        self.0
+            .descendants()
+            .filter_map(Missing::cast)
+            .find(|missing| {
+                let start = missing.0.text_range().start();
+                start >= offset
+                    && self
+                        .0
+                        .descendants_with_tokens()
+                        .filter_map(|element| element.into_token())
+                        .filter(|token| {
+                            let range = token.text_range();
+                            range.start() < start && range.end() > offset
+                        })
+                        .all(|token| token.kind().is_trivia())
+            })
+    }
+
+    /// Initial goals in source order, which is the order they are adopted,
+    /// including those nested in `begin ... end` groups.
+    pub fn initial_goals(&self) -> impl Iterator<Item = InitialGoal> {
+        self.0.descendants().filter_map(InitialGoal::cast)
+    }
+
+    /// Braced directives in source order.
+    pub fn directives(&self) -> impl Iterator<Item = Directive> {
+        children(&self.0)
+    }
+}
+
+impl Belief {
+    /// The believed literal, like `at(home)` in `at(home).`.
+    pub fn literal(&self) -> Option<Literal> {
+        child(&self.0)
+    }
+}
+
+impl InitialGoal {
+    /// The goal literal, like `start` in `!start.`.
+    pub fn literal(&self) -> Option<Literal> {
+        child(&self.0)
+    }
+
+    /// Annotations of the goal literal, like `source(self)` in
+    /// `!start[source(self)].`.
+    pub fn annotations(&self) -> impl Iterator<Item = Term> {
+        self.literal()
+            .and_then(|literal| literal.annotations())
+            .into_iter()
+            .flat_map(|annotations| annotations.terms())
+    }
+}
+
+impl Directive {
+    /// The directive name, like `include` in `{ include("lib.asl") }`, or
+    /// the `begin` and `end` keywords of `{ begin ... }` and `{ end }`.
+    pub fn name(&self) -> Option<SyntaxToken> {
+        self.0
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .find(|token| {
+                matches!(
+                    token.kind(),
+                    SyntaxKind::Include | SyntaxKind::Begin | SyntaxKind::End | SyntaxKind::Functor
+                )
+            })
+    }
+
+    pub fn terms(&self) -> Option<LiteralTerms> {
+        child(&self.0)
+    }
+
+    /// The literal after `begin`, like `namespace(ns)` in
+    /// `{ begin namespace(ns) }`.
+    pub fn literal(&self) -> Option<Literal> {
+        child(&self.0)
+    }
+
+    /// The file named by an `include` directive, without quotes.
+    pub fn include_path(&self) -> Option<String> {
+        if self.name()?.kind() != SyntaxKind::Include {
+            return None;
+        }
+        match self.terms()?.terms().next()? {
+            Term::Primitive(token) if token.kind() == SyntaxKind::String => {
+                Some(unescape(token.text()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Plan {
+    /// The triggering literal, like `move(X, Y)` in `+!move(X, Y)`.
+    pub fn trigger(&self) -> Option<Literal> {
+        child(&self.0)
+    }
+
+    pub fn trigger_kind(&self) -> Option<TriggerKind> {
+        let mut tokens = self
+            .0
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter(|token| !token.kind().is_trivia());
+        let operation = tokens.next()?.kind();
+        let achieve = tokens.next().map(|token| token.kind()) == Some(SyntaxKind::Bang);
+        Some(match (operation, achieve) {
+            (SyntaxKind::Plus, false) => TriggerKind::BeliefAdd,
+            (SyntaxKind::Minus, false) => TriggerKind::BeliefDel,
+            (SyntaxKind::Plus, true) => TriggerKind::AchieveAdd,
+            (SyntaxKind::Minus, true) => TriggerKind::AchieveDel,
+            _ => return None,
+        })
+    }
+
+    /// Trigger kind and the trigger's [`Literal::signature`], as used to
+    /// index plans.
+    pub fn trigger_signature(&self) -> Option<(TriggerKind, String, usize)> {
+        let (functor, arity) = self.trigger()?.signature()?;
+        Some((self.trigger_kind()?, functor, arity))
+    }
+
+    /// Annotations written directly on the plan.
+    pub fn annotations(&self) -> impl Iterator<Item = Term> {
+        children::<PlanAnnotation>(&self.0).flat_map(|annotation| annotation.annotations())
+    }
+
+    /// Annotations of the plan including those inherited from enclosing
+    /// `begin`/`end` groups, innermost first.
+    pub fn effective_annotations(&self) -> Vec<Term> {
+        let mut annotations: Vec<Term> = self.annotations().collect();
+        for group in self.0.ancestors().filter_map(PlanGroup::cast) {
+            annotations.extend(group.annotations());
+        }
+        annotations
+    }
+
+    pub fn is_atomic(&self) -> bool {
+        self.effective_annotations().iter().any(|annotation| {
+            matches!(annotation, Term::Literal(literal)
+                if literal.functor().is_some_and(|f| f.text() == "atomic")
+                    && literal.terms().is_none())
+        })
+    }
+
+    /// The first `priority(N)` annotation, with inner annotations taking
+    /// precedence over inherited ones.
+    pub fn priority(&self) -> Option<i64> {
+        self.effective_annotations()
+            .iter()
+            .find_map(|annotation| match annotation {
+                Term::Literal(literal)
+                    if literal.functor().is_some_and(|f| f.text() == "priority") =>
+                {
+                    match literal.terms()?.terms().next()? {
+                        Term::Primitive(token) if token.kind() == SyntaxKind::Integer => {
+                            token.text().parse().ok()
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+    }
+
+    pub fn context(&self) -> Option<PlanContext> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+
+    /// A copy of the whole tree with the body of this plan replaced by
+    /// `body`, which may come from another tree. Everything else, including
+    /// trivia, is shared with the original. `None` if the plan has no body.
+    pub fn with_body(&self, body: &Body) -> Option<SyntaxNode> {
+        let green = self.body()?.0.replace_with(body.0.green().into_owned());
+        Some(SyntaxNode::new_root(green))
+    }
+}
+
+impl PlanGroup {
+    pub fn annotations(&self) -> impl Iterator<Item = Term> {
+        children::<PlanAnnotation>(&self.0).flat_map(|annotation| annotation.annotations())
+    }
+
+    pub fn plans(&self) -> impl Iterator<Item = Plan> {
+        children(&self.0)
+    }
+}
+
+impl PlanAnnotation {
+    /// The plan label, like `l` in `@l[atomic]`.
+    pub fn label(&self) -> Option<Literal> {
+        child(&self.0)
+    }
+
+    pub fn annotations(&self) -> impl Iterator<Item = Term> {
+        self.label()
+            .and_then(|label| label.annotations())
+            .or_else(|| child(&self.0))
+            .into_iter()
+            .flat_map(|annotations| annotations.terms())
+    }
+}
+
+impl PlanContext {
+    /// The logical term that must hold for the plan to be applicable.
+    pub fn term(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+}
+
+impl Body {
+    /// The `;`-separated statements, in execution order.
+    pub fn statements(&self) -> impl Iterator<Item = Statement> {
+        children(&self.0)
+    }
+
+    /// The plain formulas among the statements, skipping control-flow
+    /// blocks.
+    pub fn formulas(&self) -> impl Iterator<Item = Formula> {
+        children(&self.0)
+    }
+
+    /// The number of statements, counting each control-flow block as one.
+    pub fn len(&self) -> usize {
+        self.statements().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements().next().is_none()
+    }
+}
+
+impl Formula {
+    pub fn kind(&self) -> FormulaType {
+        self.0
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .find(|token| !token.kind().is_trivia())
+            .and_then(|token| token.kind().formula_type())
+            .unwrap_or(FormulaType::Term)
+    }
+
+    pub fn term(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+
+    /// The goal or belief a prefixed formula applies to, like `go(X)` in
+    /// `!go(X)`. `None` for plain terms, such as actions and conditions.
+    pub fn literal(&self) -> Option<Literal> {
+        match (self.kind(), self.term()) {
+            (FormulaType::Term, _) => None,
+            (_, Some(Term::Literal(literal))) => Some(literal),
+            _ => None,
+        }
+    }
+}
+
+impl WhileLoop {
+    /// The parenthesized condition, checked before each iteration.
+    pub fn condition(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+
+    /// The block repeated while the condition holds.
+    pub fn body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+}
+
+impl ForLoop {
+    /// The query whose solutions drive the loop.
+    pub fn condition(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+
+    /// The block run once for each solution of the query.
+    pub fn body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+}
+
+impl IfThenElse {
+    /// The parenthesized condition after `if` or `elif`.
+    pub fn condition(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+
+    /// The block run if the condition holds.
+    pub fn then_body(&self) -> Option<Body> {
+        child(&self.0)
+    }
+
+    /// The block after `else`, if the branch is a plain block.
+    pub fn else_body(&self) -> Option<Body> {
+        children(&self.0).nth(1)
+    }
+
+    /// The nested conditional for `elif` or `else if`.
+    pub fn else_if(&self) -> Option<IfThenElse> {
+        child(&self.0)
+    }
+}
+
+impl Literal {
+    pub fn functor(&self) -> Option<SyntaxToken> {
+        self.0
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .find(|token| token.kind() == SyntaxKind::Functor)
+    }
+
+    pub fn terms(&self) -> Option<LiteralTerms> {
+        child(&self.0)
+    }
+
+    pub fn arity(&self) -> usize {
+        self.terms().map_or(0, |terms| terms.terms().count())
+    }
+
+    pub fn annotations(&self) -> Option<LiteralAnnotations> {
+        child(&self.0)
+    }
+
+    /// Functor and arity, with a `~` prefix on the functor if the literal is
+    /// strongly negated, since `p` and `~p` are different predicates.
+    pub fn signature(&self) -> Option<(String, usize)> {
+        let functor = self.functor()?;
+        let functor = if self.is_strongly_negated() {
+            format!("~{}", functor.text())
+        } else {
+            functor.text().to_owned()
+        };
+        Some((functor, self.arity()))
+    }
+
+    /// Whether the literal is strongly negated with `~`, as opposed to
+    /// negation as failure with `not`.
+    pub fn is_strongly_negated(&self) -> bool {
+        self.0
+            .children_with_tokens()
+            .filter_map(|element| element.into_token())
+            .any(|token| token.kind() == SyntaxKind::Tilde)
+    }
+}
+
+impl LiteralTerms {
+    pub fn terms(&self) -> impl Iterator<Item = Term> {
+        terms(&self.0)
+    }
+}
+
+impl LiteralAnnotations {
+    pub fn terms(&self) -> impl Iterator<Item = Term> {
+        terms(&self.0)
+    }
+}
+
+impl Negation {
+    pub fn term(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+}
+
+impl UnaryExpression {
+    pub fn op(&self) -> Option<UnaryOperator> {
+        token(&self.0, SyntaxKind::unary_operator)
+    }
+
+    pub fn operand(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+}
+
+macro_rules! binary_node {
+    ($name:ident) => {
+        impl $name {
+            pub fn lhs(&self) -> Option<Term> {
+                terms(&self.0).next()
+            }
+
+            pub fn rhs(&self) -> Option<Term> {
+                terms(&self.0).nth(1)
+            }
+        }
+    };
+}
+
+binary_node!(Disjunction);
+binary_node!(Conjunction);
+binary_node!(Comparison);
+binary_node!(ShiftExpression);
+binary_node!(AdditiveExpression);
+binary_node!(MultiplicativeExpression);
+binary_node!(Exponentiation);
+
+macro_rules! associative_node {
+    ($name:ident) => {
+        impl $name {
+            /// The operands, left to right. Nested chains of the same
+            /// operator are flattened, so `a | b | c` has three operands.
+            pub fn operands(&self) -> impl Iterator<Item = Term> {
+                fn collect(node: &$name, out: &mut Vec<Term>) {
+                    for term in terms(&node.0) {
+                        match term {
+                            Term::$name(inner) => collect(&inner, out),
+                            term => out.push(term),
+                        }
+                    }
+                }
+
+                let mut out = Vec::new();
+                collect(self, &mut out);
+                out.into_iter()
+            }
+        }
+    };
+}
+
+associative_node!(Disjunction);
+associative_node!(Conjunction);
+
+impl List {
+    /// The elements before the tail, if any.
+    pub fn elements(&self) -> impl Iterator<Item = Term> {
+        terms(&self.0)
+    }
+
+    pub fn tail(&self) -> Option<ListTail> {
+        child(&self.0)
+    }
+}
+
+impl ListTail {
+    pub fn term(&self) -> Option<Term> {
+        terms(&self.0).next()
+    }
+}
+
+impl Comparison {
+    pub fn op(&self) -> Option<ComparisonOperator> {
+        token(&self.0, SyntaxKind::comparison_operator)
+    }
+}
+
+impl AdditiveExpression {
+    pub fn op(&self) -> Option<AdditiveOperator> {
+        token(&self.0, SyntaxKind::additive_operator)
+    }
+}
+
+impl MultiplicativeExpression {
+    pub fn op(&self) -> Option<MultiplicativeOperator> {
+        token(&self.0, SyntaxKind::multiplicative_operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse, syntax::LexedStr};
+
+    fn parse_root(source: &str) -> SyntaxNode {
+        let lexed = LexedStr::new(source);
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert!(!parsed.unexpected_eof);
+        parsed.syntax()
+    }
+
+    #[test]
+    fn test_root_items() {
+        let lexed = LexedStr::new(
+            "at(home).\nnear(X) :- at(X).\n!start.\n+!start <- .print(hi).\nbegin +b <- x. end\n",
+        );
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+
+        let kinds: Vec<_> = parsed
+            .root()
+            .items()
+            .map(|item| item.syntax().kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                SyntaxKind::Belief,
+                SyntaxKind::Rule,
+                SyntaxKind::InitialGoal,
+                SyntaxKind::Plan,
+                SyntaxKind::PlanGroup,
+            ]
+        );
+        assert!(matches!(parsed.root().items().nth(3), Some(Item::Plan(_))));
+    }
+
+    fn parse_plan(source: &str) -> Plan {
+        parse_root(source).children().find_map(Plan::cast).unwrap()
+    }
+
+    #[test]
+    fn test_initial_goal_order() {
+        let goals = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            parsed
+                .root()
+                .initial_goals()
+                .map(|goal| goal.literal().unwrap().functor().unwrap().text().to_owned())
+                .collect()
+        };
+
+        assert_eq!(goals("!a. !b. !c."), ["a", "b", "c"]);
+        assert_eq!(
+            goals("!c.\nat(home).\n!a.\n+!a <- x.\nnear(X) :- at(X).\n!b(1)."),
+            ["c", "a", "b"]
+        );
+        assert_eq!(
+            goals("!a.\n@[atomic] begin\n!b.\n+!b <- x.\nbegin !c. end\nend\n!d."),
+            ["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_directives() {
+        let root = parse_root(
+            "{ include(\"lib/common.asl\") }\n\
+             { register_function(\"math.sq\", 1) }\n\
+             +!go <- x.",
+        );
+        let directives: Vec<Directive> = root.children().filter_map(Directive::cast).collect();
+        assert_eq!(directives.len(), 2);
+
+        assert_eq!(directives[0].name().unwrap().kind(), SyntaxKind::Include);
+        assert_eq!(
+            directives[0].include_path().as_deref(),
+            Some("lib/common.asl")
+        );
+
+        assert_eq!(directives[1].name().unwrap().text(), "register_function");
+        assert_eq!(directives[1].terms().unwrap().terms().count(), 2);
+        assert_eq!(directives[1].include_path(), None);
+
+        let root = parse_root("{ begin }\n+!go <- x.\n{ end }\n{ begin namespace(ns) }\n");
+        let names: Vec<(SyntaxKind, Option<String>)> = root
+            .children()
+            .filter_map(Directive::cast)
+            .map(|directive| {
+                (
+                    directive.name().unwrap().kind(),
+                    directive
+                        .literal()
+                        .map(|literal| literal.syntax().to_string().trim().to_owned()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (SyntaxKind::Begin, None),
+                (SyntaxKind::End, None),
+                (SyntaxKind::Begin, Some("namespace(ns)".to_owned())),
+            ]
+        );
+
+        let lexed = LexedStr::new("{ 42 }\n+!go <- x.");
+        let parsed = parse(&lexed);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.root().directives().count(), 1);
+        assert_eq!(parsed.root().items().count(), 2);
+    }
+
+    #[test]
+    fn test_initial_goal_annotations() {
+        let goal = |source: &str| -> InitialGoal {
+            let lexed = LexedStr::new(source);
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            parsed.root().initial_goals().next().unwrap()
+        };
+
+        let annotated = goal("!start[source(self)].");
+        assert_eq!(
+            annotated.literal().unwrap().functor().unwrap().text(),
+            "start"
+        );
+        let annotations: Vec<String> = annotated
+            .annotations()
+            .map(|term| term.text_range())
+            .map(|range| "!start[source(self)]."[range].to_owned())
+            .collect();
+        assert_eq!(annotations, ["source(self)"]);
+
+        assert_eq!(goal("!start.").annotations().count(), 0);
+        assert_eq!(goal("!start(1)[a, b].").annotations().count(), 2);
+    }
+
+    #[test]
+    fn test_missing() {
+        let expected_at = |source: &str, offset: u32| -> Option<Expected> {
+            let lexed = LexedStr::new(source);
+            parse(&lexed)
+                .root()
+                .missing_at(offset.into())
+                .map(|missing| missing.expected())
+        };
+
+        let source = "+!go : a & ";
+        assert_eq!(expected_at(source, 10), Some(Expected::Term));
+        assert_eq!(expected_at(source, 11), Some(Expected::Term));
+        assert_eq!(expected_at(source, 4), None);
+
+        assert_eq!(expected_at("foo(", 4), Some(Expected::Term));
+        assert_eq!(expected_at("+!", 2), Some(Expected::Literal));
+        assert_eq!(expected_at("+!go : ", 7), Some(Expected::Context));
+        assert_eq!(expected_at("+!go <- a; ", 11), Some(Expected::Formula));
+        assert_eq!(expected_at("+!go <- a; .", 12), None);
+
+        let lexed = LexedStr::new("+!go : a & <- x.");
+        let parsed = parse(&lexed);
+        let missing = parsed.root().missing_at(10.into()).unwrap();
+        assert_eq!(missing.syntax().text_range(), TextRange::empty(11.into()));
+        assert!(!parsed.errors.is_empty());
+    }
+
+    #[test]
+    fn test_strong_negation() {
+        let root = parse_root("~busy.\n+~busy <- relax; -~busy.\nidle :- not busy.");
+        let mut items = root.children().filter_map(Item::cast);
+
+        let Some(Item::Belief(belief)) = items.next() else {
+            panic!("expected belief");
+        };
+        let literal = belief.literal().unwrap();
+        assert!(literal.is_strongly_negated());
+        assert_eq!(literal.functor().unwrap().text(), "busy");
+
+        let Some(Item::Plan(plan)) = items.next() else {
+            panic!("expected plan");
+        };
+        assert!(plan.trigger().unwrap().is_strongly_negated());
+        assert_eq!(
+            plan.trigger_signature(),
+            Some((TriggerKind::BeliefAdd, "~busy".to_owned(), 0))
+        );
+        assert_ne!(
+            plan.trigger_signature(),
+            parse_plan("+busy <- work.").trigger_signature()
+        );
+        let literals: Vec<Literal> = plan
+            .body()
+            .unwrap()
+            .syntax()
+            .descendants()
+            .filter_map(Literal::cast)
+            .collect();
+        assert_eq!(literals.len(), 2);
+        assert!(!literals[0].is_strongly_negated());
+        assert!(literals[1].is_strongly_negated());
+
+        let Some(Item::Rule(rule)) = items.next() else {
+            panic!("expected rule");
+        };
+        let negated = rule.syntax().descendants().find_map(Literal::cast).unwrap();
+        assert!(!negated.is_strongly_negated());
+    }
+
+    #[test]
+    fn test_body_formulas() {
+        let plan = parse_plan("+!go <- !a; ?b; +c.");
+        let body = plan.body().unwrap();
+        assert_eq!(body.len(), 3);
+        assert_eq!(
+            body.formulas().map(|f| f.kind()).collect::<Vec<_>>(),
+            [FormulaType::Achieve, FormulaType::Test, FormulaType::Add]
+        );
+        assert_eq!(
+            body.formulas()
+                .map(|f| f.term().unwrap().syntax().to_string().trim().to_owned())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_formula_literal() {
+        let plan = parse_plan("+!run <- !go(X); ?done; act; X = 1.");
+        let formulas: Vec<(FormulaType, Option<String>)> = plan
+            .body()
+            .unwrap()
+            .formulas()
+            .map(|formula| {
+                (
+                    formula.kind(),
+                    formula
+                        .literal()
+                        .map(|literal| literal.syntax().to_string().trim().to_owned()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            formulas,
+            [
+                (FormulaType::Achieve, Some("go(X)".to_owned())),
+                (FormulaType::Test, Some("done".to_owned())),
+                (FormulaType::Term, None),
+                (FormulaType::Term, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_body() {
+        let source = "// agent\n+!a <- x.\n\n+!b : c <- y; z.\n";
+        let root = parse_root(source);
+        let plans: Vec<Plan> = root.children().filter_map(Plan::cast).collect();
+        let body = parse_plan("+!t <- go(1);\n    go(2).").body().unwrap();
+
+        let edited = plans[1].with_body(&body).unwrap();
+        assert_eq!(
+            edited.to_string(),
+            "// agent\n+!a <- x.\n\n+!b : c <- go(1);\n    go(2).\n"
+        );
+        let prefix = plans[1].body().unwrap().syntax().text_range().start();
+        assert_eq!(
+            edited
+                .descendants_with_tokens()
+                .filter_map(|element| element.into_token())
+                .take_while(|token| token.text_range().start() < prefix)
+                .map(|token| token.to_string())
+                .collect::<String>(),
+            source[..usize::from(prefix)]
+        );
+        assert_eq!(root.to_string(), source);
+
+        let edited = plans[0].with_body(&body).unwrap();
+        assert_eq!(
+            edited.to_string(),
+            "// agent\n+!a <- go(1);\n    go(2).\n\n+!b : c <- y; z.\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_formula() {
+        let plan = parse_plan("+tick : count(N) <- -+count(N + 1).");
+        let body = plan.body().unwrap();
+        assert_eq!(body.len(), 1);
+        let formula = body.formulas().next().unwrap();
+        assert_eq!(formula.kind(), FormulaType::Replace);
+        let literal = formula.literal().unwrap();
+        assert_eq!(literal.functor().unwrap().text(), "count");
+        assert_eq!(literal.arity(), 1);
+    }
+
+    #[test]
+    fn test_context_conjunction() {
+        let plan = parse_plan("+!go : a & b <- act.");
+        let term = plan.context().unwrap().term().unwrap();
+        assert!(matches!(term, Term::Conjunction(_)));
+    }
+
+    #[test]
+    fn test_operands() {
+        let context = |source| parse_plan(source).context().unwrap().term().unwrap();
+
+        let Term::Disjunction(disjunction) = context("+!go : a | b | c <- act.") else {
+            panic!("expected disjunction");
+        };
+        let operands: Vec<_> = disjunction
+            .operands()
+            .map(|term| term.text_range())
+            .collect();
+        assert_eq!(
+            operands,
+            [
+                TextRange::new(7.into(), 8.into()),
+                TextRange::new(11.into(), 12.into()),
+                TextRange::new(15.into(), 16.into()),
+            ]
+        );
+
+        let Term::Conjunction(conjunction) = context("+!go : a & (b | c) <- act.") else {
+            panic!("expected conjunction");
+        };
+        let operands: Vec<_> = conjunction.operands().collect();
+        assert_eq!(operands.len(), 2);
+        assert!(matches!(operands[0], Term::Literal(_)));
+        let Term::Disjunction(inner) = &operands[1] else {
+            panic!("expected disjunction");
+        };
+        assert_eq!(inner.operands().count(), 2);
+    }
+
+    #[test]
+    fn test_plan_group_annotations() {
+        let root = parse_root(
+            "@[atomic] begin\n\
+             +!a <- x.\n\
+             @b[priority(2)] +!b <- y.\n\
+             end\n\
+             +!c <- z.",
+        );
+
+        let plans: Vec<Plan> = root.descendants().filter_map(Plan::cast).collect();
+        assert_eq!(plans.len(), 3);
+
+        assert!(plans[0].is_atomic());
+        assert_eq!(plans[0].annotations().count(), 0);
+        assert_eq!(plans[0].effective_annotations().len(), 1);
+        assert_eq!(plans[0].priority(), None);
+
+        assert!(plans[1].is_atomic());
+        assert_eq!(plans[1].effective_annotations().len(), 2);
+        assert_eq!(plans[1].priority(), Some(2));
+
+        assert!(!plans[2].is_atomic());
+    }
+
+    #[test]
+    fn test_trigger_signature() {
+        assert_eq!(
+            parse_plan("+!move(X, Y) <- go(X); go(Y).").trigger_signature(),
+            Some((TriggerKind::AchieveAdd, "move".to_owned(), 2))
+        );
+        assert_eq!(
+            parse_plan("-belief <- act.").trigger_signature(),
+            Some((TriggerKind::BeliefDel, "belief".to_owned(), 0))
+        );
+        assert_eq!(
+            parse_plan("@label[atomic] -!g(1) <- act.").trigger_signature(),
+            Some((TriggerKind::AchieveDel, "g".to_owned(), 1))
+        );
+    }
+
+    #[test]
+    fn test_plan_parts() {
+        let plan = parse_plan("+!move(X, Y) : at(X) & free(Y) <- go(Y).");
+        let trigger = plan.trigger().unwrap();
+        assert_eq!(trigger.functor().unwrap().text(), "move");
+        assert_eq!(trigger.arity(), 2);
+        assert_eq!(plan.trigger_kind(), Some(TriggerKind::AchieveAdd));
+
+        let Term::Conjunction(context) = plan.context().unwrap().term().unwrap() else {
+            panic!("expected conjunction");
+        };
+        assert_eq!(
+            context
+                .operands()
+                .map(|term| term.syntax().to_string().trim().to_owned())
+                .collect::<Vec<_>>(),
+            ["at(X)", "free(Y)"]
+        );
+        assert_eq!(plan.body().unwrap().len(), 1);
+
+        assert_eq!(parse_plan("+b <- act.").context(), None);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let plan = parse_plan(
+            "+!g <- while (N < 3) { a; b }; for (.member(X, L)) { c }; \
+             if (x) { d } elif (y) { e } else { f; g }.",
+        );
+        let body = plan.body().unwrap();
+        assert_eq!(body.len(), 3);
+        assert_eq!(body.formulas().count(), 0);
+        let mut statements = body.statements();
+        let Some(Statement::WhileLoop(while_loop)) = statements.next() else {
+            panic!("expected while");
+        };
+        let Some(Statement::ForLoop(for_loop)) = statements.next() else {
+            panic!("expected for");
+        };
+        let Some(Statement::IfThenElse(if_then_else)) = statements.next() else {
+            panic!("expected if");
+        };
+        assert_eq!(statements.next(), None);
+
+        assert!(matches!(while_loop.condition(), Some(Term::Comparison(_))));
+        assert_eq!(while_loop.body().unwrap().len(), 2);
+
+        let Some(Term::Literal(query)) = for_loop.condition() else {
+            panic!("expected literal");
+        };
+        assert_eq!(query.functor().unwrap().text(), ".member");
+        assert_eq!(for_loop.body().unwrap().len(), 1);
+
+        assert_eq!(if_then_else.then_body().unwrap().len(), 1);
+        assert_eq!(if_then_else.else_body(), None);
+        let elif = if_then_else.else_if().unwrap();
+        assert_eq!(elif.condition().unwrap().syntax().to_string(), "y");
+        assert_eq!(elif.then_body().unwrap().len(), 1);
+        assert_eq!(elif.else_body().unwrap().len(), 2);
+        assert_eq!(elif.else_if(), None);
+    }
+}
@@ -0,0 +1,189 @@
+use codespan_reporting::{
+    diagnostic::{self, Diagnostic, Label},
+    files::Files,
+    term,
+    term::termcolor::NoColor,
+};
+
+use crate::{
+    analysis,
+    error::Severity,
+    parser::{parse, Parsed},
+    syntax::{LexedStr, SyntaxNode},
+};
+
+impl From<Severity> for diagnostic::Severity {
+    fn from(severity: Severity) -> diagnostic::Severity {
+        match severity {
+            Severity::Info => diagnostic::Severity::Note,
+            Severity::Warning => diagnostic::Severity::Warning,
+            Severity::Error => diagnostic::Severity::Error,
+        }
+    }
+}
+
+/// Lexes and parses `source` and collects all errors, ordered by position.
+/// Labels refer to the single file `()`, so the result can be rendered
+/// against a [`SimpleFile`](codespan_reporting::files::SimpleFile).
+pub fn validate(source: &str) -> Vec<Diagnostic<()>> {
+    let lexed = LexedStr::new(source);
+    validate_parsed(&lexed, &parse(&lexed))
+}
+
+/// Like [`validate`], for input that was already lexed and parsed.
+pub fn validate_parsed(lexed: &LexedStr<'_>, parsed: &Parsed) -> Vec<Diagnostic<()>> {
+    let mut diagnostics = Vec::new();
+
+    for error in &lexed.errors {
+        diagnostics.push(
+            Diagnostic::error()
+                .with_message(error.kind.to_string())
+                .with_labels(vec![Label::primary((), lexed.token_range(error.token_idx))]),
+        );
+    }
+
+    for error in &parsed.errors {
+        diagnostics.push(
+            Diagnostic::new(error.severity.into())
+                .with_message(error.to_string())
+                .with_labels(vec![Label::primary((), lexed.token_range(error.token_idx))]),
+        );
+    }
+
+    if parsed.unexpected_eof {
+        let last = lexed.text.len().saturating_sub(1);
+        diagnostics.push(
+            Diagnostic::error()
+                .with_message("unexpected end of file")
+                .with_labels(vec![Label::primary((), last..last)]),
+        );
+    }
+
+    diagnostics.sort_by_key(|diagnostic| {
+        diagnostic
+            .labels
+            .first()
+            .map_or(0, |label| label.range.start)
+    });
+    diagnostics
+}
+
+/// Runs all lints on `source`. Like [`validate`], labels refer to the
+/// single file `()`.
+pub fn lint(source: &str) -> Vec<Diagnostic<()>> {
+    lint_parsed(&parse(&LexedStr::new(source)).syntax())
+}
+
+/// Like [`lint`], for an already parsed tree.
+pub fn lint_parsed(root: &SyntaxNode) -> Vec<Diagnostic<()>> {
+    analysis::check_left_recursion(root)
+        .into_iter()
+        .chain(analysis::check_suspicious_unification(root))
+        .chain(analysis::check_duplicate_plans(root))
+        .chain(analysis::check_floundering(root))
+        .chain(analysis::check_contradictory_context(root))
+        .chain(analysis::check_heads(root))
+        .chain(analysis::check_unreachable_formulas(root))
+        .map(|warning| {
+            Diagnostic::new(warning.severity.into())
+                .with_message(warning.message)
+                .with_labels(vec![Label::primary((), warning.range)])
+        })
+        .collect()
+}
+
+/// Whether any of the diagnostics is an error, as opposed to warnings and
+/// notes.
+pub fn has_errors<FileId>(diagnostics: &[Diagnostic<FileId>]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity >= diagnostic::Severity::Error)
+}
+
+/// Renders `diagnostic` like the command line does, but to a plain string
+/// without colors.
+pub fn render_diagnostic<'a, F: Files<'a>>(
+    diagnostic: &Diagnostic<F::FileId>,
+    files: &'a F,
+) -> String {
+    let mut writer = NoColor::new(Vec::new());
+    term::emit(&mut writer, &term::Config::default(), files, diagnostic)
+        .expect("render diagnostic");
+    String::from_utf8(writer.into_inner()).expect("diagnostic is utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan_reporting::files::SimpleFile;
+
+    use super::*;
+
+    fn messages(source: &str) -> Vec<(String, &str)> {
+        validate(source)
+            .into_iter()
+            .map(|diagnostic| {
+                let range = diagnostic.labels[0].range.clone();
+                (diagnostic.message, &source[range])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render_diagnostic() {
+        let source = "at(home)\n+!go <- move.\n";
+        let file = SimpleFile::new("agent.asl", source);
+        let diagnostics = validate(source);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            render_diagnostic(&diagnostics[0], &file),
+            "error: expected '.' after rule or belief\n\
+             \x20 ┌─ agent.asl:2:1\n\
+             \x20 │\n\
+             2 │ +!go <- move.\n\
+             \x20 │ ^\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn test_severity() {
+        let source = "p :- q & 1 = 1.\nq(a, ).\n";
+        let errors = validate(source);
+        let warnings = lint(source);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|diagnostic| diagnostic.severity)
+                .collect::<Vec<_>>(),
+            [diagnostic::Severity::Error]
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|diagnostic| diagnostic.severity)
+                .collect::<Vec<_>>(),
+            [diagnostic::Severity::Warning]
+        );
+        assert!(has_errors(&errors));
+        assert!(!has_errors(&warnings));
+        assert!(lint("+!go <- move.").is_empty());
+
+        let lexed = LexedStr::new(source);
+        let parsed = parse(&lexed);
+        assert_eq!(validate_parsed(&lexed, &parsed), errors);
+        assert_eq!(lint_parsed(&parsed.syntax()), warnings);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(messages("+!go <- move.").is_empty());
+        assert_eq!(
+            messages("p(a, ) :- q.\n+!go <- .print(\"unterminated).\n"),
+            [
+                ("trailing comma before ')'".to_owned(), ","),
+                ("unterminated string".to_owned(), "\"unterminated).",),
+                ("unexpected end of file".to_owned(), ""),
+            ]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::{error::Error, fmt};
+
+use crate::{lower::LowerError, parser::ParserError, syntax::SyntaxError};
+
+/// How serious a diagnostic is. Only errors make a program invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug)]
+pub enum PheresError {
+    Syntax(Vec<SyntaxError>),
+    Parser(Vec<ParserError>),
+    UnexpectedEof,
+    Lower(LowerError),
+    /// A term that was required to be ground contains variables.
+    NotGround,
+}
+
+impl fmt::Display for PheresError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PheresError::Syntax(errors) => match errors.first() {
+                Some(error) => error.kind.fmt(f),
+                None => f.write_str("syntax error"),
+            },
+            PheresError::Parser(errors) => match errors.first() {
+                Some(error) => error.fmt(f),
+                None => f.write_str("parser error"),
+            },
+            PheresError::UnexpectedEof => f.write_str("unexpected end of file"),
+            PheresError::Lower(error) => error.fmt(f),
+            PheresError::NotGround => f.write_str("expected a ground term without variables"),
+        }
+    }
+}
+
+impl Error for PheresError {}
+
+impl From<LowerError> for PheresError {
+    fn from(error: LowerError) -> PheresError {
+        PheresError::Lower(error)
+    }
+}
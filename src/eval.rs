@@ -0,0 +1,556 @@
+use std::{cmp::Ordering, fmt};
+
+use crate::runtime::{BinaryOperator, UnaryOparator, Value, VariableId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Unbound(VariableId),
+    TypeError,
+    DivisionByZero,
+    Overflow,
+    Unsupported,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            EvalError::Unbound(_) => "unbound variable",
+            EvalError::TypeError => "type error",
+            EvalError::DivisionByZero => "division by zero",
+            EvalError::Overflow => "integer overflow",
+            EvalError::Unsupported => "cannot evaluate term",
+        })
+    }
+}
+
+/// An [`EvalError`] with the path of the offending subterm, in the format of
+/// [`Spans`](crate::lower::Spans).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatedEvalError {
+    pub error: EvalError,
+    pub path: Vec<usize>,
+}
+
+impl fmt::Display for LocatedEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+/// Evaluates arithmetic operators in a ground value. Values without
+/// operators are returned unchanged.
+pub fn eval_arithmetic(value: &Value) -> Result<Value, EvalError> {
+    eval_arithmetic_located(value).map_err(|located| located.error)
+}
+
+/// Like [`eval_arithmetic`], but reports which subterm failed. Type errors
+/// point at the operand that is not a number.
+pub fn eval_arithmetic_located(value: &Value) -> Result<Value, LocatedEvalError> {
+    Evaluator { path: Vec::new() }.arithmetic(value)
+}
+
+struct Evaluator {
+    path: Vec<usize>,
+}
+
+impl Evaluator {
+    fn error(&self, error: EvalError) -> LocatedEvalError {
+        LocatedEvalError {
+            error,
+            path: self.path.clone(),
+        }
+    }
+
+    fn operand(&mut self, index: usize, value: &Value) -> Result<Value, LocatedEvalError> {
+        self.path.push(index);
+        let res = self.arithmetic(value).and_then(|value| match value {
+            Value::Integer(_) | Value::Float(_) => Ok(value),
+            _ => Err(self.error(EvalError::TypeError)),
+        });
+        self.path.pop();
+        res
+    }
+
+    fn arithmetic(&mut self, value: &Value) -> Result<Value, LocatedEvalError> {
+        match value {
+            Value::Variable(id) => Err(self.error(EvalError::Unbound(*id))),
+            Value::UnaryOp {
+                op: UnaryOparator::Pos,
+                value,
+            } => self.operand(0, value),
+            Value::UnaryOp {
+                op: UnaryOparator::Neg,
+                value,
+            } => match self.operand(0, value)? {
+                Value::Integer(n) => n
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or_else(|| self.error(EvalError::Overflow)),
+                Value::Float(f) => Ok(Value::Float(-f)),
+                _ => unreachable!("operand is a number"),
+            },
+            Value::BinaryOp { op, left, right } if is_arithmetic(*op) => {
+                let left = self.operand(0, left)?;
+                let right = self.operand(1, right)?;
+                arithmetic(*op, left, right).map_err(|error| self.error(error))
+            }
+            _ => Ok(value.clone()),
+        }
+    }
+}
+
+fn is_arithmetic(op: BinaryOperator) -> bool {
+    matches!(
+        op,
+        BinaryOperator::Plus
+            | BinaryOperator::Minus
+            | BinaryOperator::Mul
+            | BinaryOperator::Div
+            | BinaryOperator::FloorDiv
+            | BinaryOperator::Mod
+            | BinaryOperator::Pow
+    )
+}
+
+fn arithmetic(op: BinaryOperator, left: Value, right: Value) -> Result<Value, EvalError> {
+    Ok(match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => match op {
+            BinaryOperator::Plus => Value::Integer(a.checked_add(b).ok_or(EvalError::Overflow)?),
+            BinaryOperator::Minus => Value::Integer(a.checked_sub(b).ok_or(EvalError::Overflow)?),
+            BinaryOperator::Mul => Value::Integer(a.checked_mul(b).ok_or(EvalError::Overflow)?),
+            BinaryOperator::Div if b == 0 => return Err(EvalError::DivisionByZero),
+            BinaryOperator::Div => Value::Float(a as f64 / b as f64),
+            BinaryOperator::FloorDiv | BinaryOperator::Mod if b == 0 => {
+                return Err(EvalError::DivisionByZero)
+            }
+            BinaryOperator::FloorDiv => {
+                Value::Integer(a.checked_div_euclid(b).ok_or(EvalError::Overflow)?)
+            }
+            BinaryOperator::Mod => {
+                Value::Integer(a.checked_rem_euclid(b).ok_or(EvalError::Overflow)?)
+            }
+            BinaryOperator::Pow => match u32::try_from(b) {
+                Ok(exp) => Value::Integer(a.checked_pow(exp).ok_or(EvalError::Overflow)?),
+                Err(_) => Value::Float((a as f64).powf(b as f64)),
+            },
+            _ => unreachable!("not an arithmetic operator: {:?}", op),
+        },
+        (left, right) => {
+            let a = as_float(&left)?;
+            let b = as_float(&right)?;
+            Value::Float(match op {
+                BinaryOperator::Plus => a + b,
+                BinaryOperator::Minus => a - b,
+                BinaryOperator::Mul => a * b,
+                BinaryOperator::Div | BinaryOperator::FloorDiv | BinaryOperator::Mod
+                    if b == 0.0 =>
+                {
+                    return Err(EvalError::DivisionByZero)
+                }
+                BinaryOperator::Div => a / b,
+                BinaryOperator::FloorDiv => (a / b).floor(),
+                BinaryOperator::Mod => a.rem_euclid(b),
+                BinaryOperator::Pow => a.powf(b),
+                _ => unreachable!("not an arithmetic operator: {:?}", op),
+            })
+        }
+    })
+}
+
+fn as_float(value: &Value) -> Result<f64, EvalError> {
+    match *value {
+        Value::Integer(n) => Ok(n as f64),
+        Value::Float(f) => Ok(f),
+        _ => Err(EvalError::TypeError),
+    }
+}
+
+/// Options for [`eval_condition_with_options`].
+#[derive(Debug, Default, Clone)]
+pub struct EvalOptions {
+    /// Absolute tolerance for `=:=` and `=\=` when either side is a float,
+    /// so that `0.1 + 0.2 =:= 0.3` can hold. The default of zero compares
+    /// exactly, and so does a negative or NaN tolerance. Integers are always
+    /// compared exactly.
+    pub float_tolerance: f64,
+}
+
+/// Evaluates a ground logical expression of comparisons, `&`, `|`, `not`
+/// and the constants `true` and `false`. Anything that would need a belief
+/// base to answer is [`EvalError::Unsupported`].
+pub fn eval_condition(value: &Value) -> Result<bool, EvalError> {
+    eval_condition_with_options(value, &EvalOptions::default())
+}
+
+/// Like [`eval_condition`], with [`EvalOptions`] for arithmetic
+/// comparisons.
+pub fn eval_condition_with_options(
+    value: &Value,
+    options: &EvalOptions,
+) -> Result<bool, EvalError> {
+    match value {
+        Value::Variable(id) => Err(EvalError::Unbound(*id)),
+        Value::Term {
+            strong_negation: false,
+            functor,
+            args,
+            annotations,
+        } if args.is_empty() && annotations.is_empty() => match functor.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(EvalError::Unsupported),
+        },
+        Value::UnaryOp {
+            op: UnaryOparator::Not,
+            value,
+        } => Ok(!eval_condition_with_options(value, options)?),
+        Value::BinaryOp {
+            op: BinaryOperator::And,
+            left,
+            right,
+        } => Ok(eval_condition_with_options(left, options)?
+            && eval_condition_with_options(right, options)?),
+        Value::BinaryOp {
+            op: BinaryOperator::Or,
+            left,
+            right,
+        } => Ok(eval_condition_with_options(left, options)?
+            || eval_condition_with_options(right, options)?),
+        Value::BinaryOp { op, left, right } => {
+            let left = eval_arithmetic(left)?;
+            let right = eval_arithmetic(right)?;
+            match op {
+                BinaryOperator::Eq => Ok(left == right),
+                BinaryOperator::Equal => Ok(left.eq_ignoring_annotations(&right)),
+                BinaryOperator::NotEqual => Ok(!left.eq_ignoring_annotations(&right)),
+                BinaryOperator::ArithEqual => arith_equal(&left, &right, options),
+                BinaryOperator::ArithNotEqual => Ok(!arith_equal(&left, &right, options)?),
+                BinaryOperator::Lt => Ok(compare(&left, &right)? == Ordering::Less),
+                BinaryOperator::LtEq => Ok(compare(&left, &right)? != Ordering::Greater),
+                BinaryOperator::Gt => Ok(compare(&left, &right)? == Ordering::Greater),
+                BinaryOperator::GtEq => Ok(compare(&left, &right)? != Ordering::Less),
+                _ => Err(EvalError::Unsupported),
+            }
+        }
+        _ => Err(EvalError::TypeError),
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Result<Ordering, EvalError> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        _ => compare_numbers(left, right),
+    }
+}
+
+fn arith_equal(left: &Value, right: &Value, options: &EvalOptions) -> Result<bool, EvalError> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(a == b),
+        _ if options.float_tolerance > 0.0 => {
+            Ok((as_float(left)? - as_float(right)?).abs() <= options.float_tolerance)
+        }
+        _ => Ok(compare_numbers(left, right)? == Ordering::Equal),
+    }
+}
+
+fn compare_numbers(left: &Value, right: &Value) -> Result<Ordering, EvalError> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(a.cmp(b)),
+        _ => as_float(left)?
+            .partial_cmp(&as_float(right)?)
+            .ok_or(EvalError::TypeError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{AstNode, Body, PlanContext, Term},
+        lower::{
+            lower_context, lower_formula, lower_term, lower_term_with_options,
+            lower_term_with_spans, LowerError, LowerOptions,
+        },
+        parser::{parse, parse_term},
+        runtime::{Formula, Interner},
+        syntax::{FormulaType, LexedStr, SyntaxKind},
+    };
+
+    fn lower(source: &str, options: &LowerOptions) -> Result<Value, LowerError> {
+        let lexed = LexedStr::new(source);
+        let parsed = parse_term(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let term = parsed
+            .syntax()
+            .children_with_tokens()
+            .find_map(Term::cast)
+            .unwrap();
+        lower_term_with_options(&term, &mut Interner::default(), options)
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let failing = |source: &str| -> (EvalError, String) {
+            let lexed = LexedStr::new(source);
+            let parsed = parse_term(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            let term = parsed
+                .syntax()
+                .children_with_tokens()
+                .find_map(Term::cast)
+                .unwrap();
+            let (value, spans) =
+                lower_term_with_spans(&term, &mut Interner::default(), &LowerOptions::default())
+                    .unwrap();
+            let located = eval_arithmetic_located(&value).unwrap_err();
+            let range = spans.get(&located.path).unwrap();
+            (located.error, source[range].to_owned())
+        };
+
+        assert_eq!(
+            failing("1 + \"x\""),
+            (EvalError::TypeError, "\"x\"".to_owned())
+        );
+        assert_eq!(
+            failing("2 * (foo(1) - 3)"),
+            (EvalError::TypeError, "foo(1)".to_owned())
+        );
+        assert_eq!(
+            failing("1 + (4 div (2 - 2))"),
+            (EvalError::DivisionByZero, "4 div (2 - 2)".to_owned())
+        );
+        assert_eq!(
+            failing("-(X * 2) + 1"),
+            (EvalError::Unbound(VariableId(0)), "X".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_eval_arithmetic() {
+        let options = LowerOptions::default();
+        assert_eq!(
+            eval_arithmetic(&lower("1 + 2 * 3", &options).unwrap()),
+            Ok(Value::Integer(7))
+        );
+        assert_eq!(
+            eval_arithmetic(&lower("7 div 2 + 7 mod 2", &options).unwrap()),
+            Ok(Value::Integer(4))
+        );
+        assert_eq!(
+            eval_arithmetic(&lower("1 / 2", &options).unwrap()),
+            Ok(Value::Float(0.5))
+        );
+        assert_eq!(
+            eval_arithmetic(&lower("1 div 0", &options).unwrap()),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            eval_arithmetic(&lower("1 + \"x\"", &options).unwrap()),
+            Err(EvalError::TypeError)
+        );
+    }
+
+    #[test]
+    fn test_arith_equality() {
+        let condition = |source| eval_condition(&lower(source, &LowerOptions::default()).unwrap());
+        assert_eq!(condition("1 =:= 1.0"), Ok(true));
+        assert_eq!(condition("1 == 1.0"), Ok(false));
+        assert_eq!(condition("1 =\\= 1.0"), Ok(false));
+        assert_eq!(condition("1 \\== 1.0"), Ok(true));
+        assert_eq!(condition("2 * 3 =:= 6.0"), Ok(true));
+        assert_eq!(condition("\"a\" =:= \"a\""), Err(EvalError::TypeError));
+    }
+
+    #[test]
+    fn test_float_tolerance() {
+        let value = lower("0.1 + 0.2 =:= 0.3", &LowerOptions::default()).unwrap();
+        assert_eq!(eval_condition(&value), Ok(false));
+
+        let tolerant = EvalOptions {
+            float_tolerance: 1e-9,
+        };
+        assert_eq!(eval_condition_with_options(&value, &tolerant), Ok(true));
+
+        let condition = |source| {
+            eval_condition_with_options(
+                &lower(source, &LowerOptions::default()).unwrap(),
+                &tolerant,
+            )
+        };
+        assert_eq!(condition("0.1 + 0.2 =\\= 0.3"), Ok(false));
+        assert_eq!(condition("1 =:= 1.0000000001"), Ok(true));
+        assert_eq!(condition("0.1 + 0.2 =:= 0.31"), Ok(false));
+        assert_eq!(condition("0.1 + 0.2 == 0.3"), Ok(false));
+
+        for float_tolerance in [-1.0, f64::NAN] {
+            let exact = EvalOptions { float_tolerance };
+            assert_eq!(eval_condition_with_options(&value, &exact), Ok(false));
+        }
+    }
+
+    #[test]
+    fn test_equal_ignores_annotations() {
+        let condition = |source| eval_condition(&lower(source, &LowerOptions::default()).unwrap());
+        assert_eq!(
+            condition("foo[source(self)] == foo[source(percept)]"),
+            Ok(true)
+        );
+        assert_eq!(
+            condition("foo[source(self)] \\== foo[source(percept)]"),
+            Ok(false)
+        );
+        assert_eq!(condition("foo[a] == bar[a]"), Ok(false));
+        assert_eq!(condition("foo[a] \\== bar[a]"), Ok(true));
+    }
+
+    #[test]
+    fn test_chained_comparison() {
+        let desugar = LowerOptions {
+            desugar_chained_comparisons: true,
+        };
+        assert_eq!(
+            eval_condition(&lower("1 < 3 < 5", &desugar).unwrap()),
+            Ok(true)
+        );
+        assert_eq!(
+            eval_condition(&lower("1 < 7 < 5", &desugar).unwrap()),
+            Ok(false)
+        );
+        assert_eq!(
+            eval_condition(&lower("1 < 2 <= 2 < 3", &desugar).unwrap()),
+            Ok(true)
+        );
+
+        assert!(matches!(
+            lower("1 < 3 < 5", &LowerOptions::default()),
+            Err(LowerError::ChainedComparison { .. })
+        ));
+
+        let Value::BinaryOp { left, right, .. } = lower("1 < _ < 5", &desugar).unwrap() else {
+            panic!("expected conjunction");
+        };
+        let (Value::BinaryOp { right: first, .. }, Value::BinaryOp { left: second, .. }) =
+            (*left, *right)
+        else {
+            panic!("expected comparisons");
+        };
+        assert!(matches!(*first, Value::Variable(_)));
+        assert_eq!(first, second);
+
+        let source = "1 < foo * 2 < 5";
+        let lexed = LexedStr::new(source);
+        let parsed = parse_term(&lexed);
+        let term = parsed
+            .syntax()
+            .children_with_tokens()
+            .find_map(Term::cast)
+            .unwrap();
+        let (_, spans) = lower_term_with_spans(&term, &mut Interner::default(), &desugar).unwrap();
+        let text = |path: &[usize]| &source[spans.get(path).unwrap()];
+        assert_eq!(text(&[0, 1, 0]), "foo");
+        assert_eq!(text(&[1, 0, 0]), "foo");
+        assert_eq!(text(&[1]), "foo * 2 < 5");
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let options = LowerOptions::default();
+        assert_eq!(
+            lower("9223372036854775807", &options).unwrap(),
+            Value::Integer(i64::MAX)
+        );
+        assert!(matches!(
+            lower("9223372036854775808", &options),
+            Err(LowerError::InvalidNumber { .. })
+        ));
+        assert_eq!(lower("0xFf", &options).unwrap(), Value::Integer(255));
+        assert_eq!(
+            lower("1_000_000", &options).unwrap(),
+            Value::Integer(1_000_000)
+        );
+        assert_eq!(lower("2.718_5", &options).unwrap(), Value::Float(2.7185));
+        assert_eq!(lower("0o17", &options).unwrap(), Value::Integer(15));
+        assert_eq!(lower("0b1010", &options).unwrap(), Value::Integer(10));
+        assert_eq!(lower("0xFF_FF", &options).unwrap(), Value::Integer(0xFFFF));
+        assert_eq!(
+            lower("0b1010_1010", &options).unwrap(),
+            Value::Integer(0b1010_1010)
+        );
+        assert!(matches!(
+            lower("0x8000000000000000", &options),
+            Err(LowerError::InvalidNumber { .. })
+        ));
+        assert!(matches!(
+            lower(&"9".repeat(1 << 20), &options),
+            Err(LowerError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lower_context() {
+        let lexed = LexedStr::new("+!go(X) : a(X) & X > 2 & b(X) <- x.");
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let context = parsed
+            .syntax()
+            .descendants()
+            .find_map(PlanContext::cast)
+            .unwrap();
+
+        let lowered = lower_context(&context, &mut Interner::default()).unwrap();
+        assert_eq!(
+            lowered.goals,
+            [Value::parse("a(X)").unwrap(), Value::parse("b(X)").unwrap()]
+        );
+        assert_eq!(lowered.guards, [Value::parse("X > 2").unwrap()]);
+    }
+
+    #[test]
+    fn test_lower_replace_formula() {
+        let lexed = LexedStr::new("+tick : count(N) <- -+count(N + 1); +ticked.");
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let body = parsed.syntax().descendants().find_map(Body::cast).unwrap();
+
+        let mut interner = Interner::default();
+        let formulas: Vec<Formula> = body
+            .formulas()
+            .map(|formula| lower_formula(&formula, &mut interner).unwrap())
+            .collect();
+        assert_eq!(
+            formulas,
+            [
+                Formula {
+                    kind: FormulaType::Replace,
+                    term: Value::parse("count(N + 1)").unwrap(),
+                },
+                Formula {
+                    kind: FormulaType::Add,
+                    term: Value::atom("ticked"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let source = "f(a, X << 1 + 2)";
+        let lexed = LexedStr::new(source);
+        let parsed = parse_term(&lexed);
+        assert_eq!(parsed.errors.len(), 1);
+        let term = parsed
+            .syntax()
+            .children_with_tokens()
+            .find_map(Term::cast)
+            .unwrap();
+
+        let Err(LowerError::Unsupported { kind, range }) =
+            lower_term(&term, &mut Interner::default())
+        else {
+            panic!("expected unsupported construct");
+        };
+        assert_eq!(kind, SyntaxKind::ShiftExpression);
+        assert_eq!(&source[range], "X << 1 + 2");
+    }
+}
@@ -0,0 +1,309 @@
+//! Conversion between [`Value`] and JSON with a fixed schema, for hosts that
+//! do not speak AgentSpeak:
+//!
+//! - integers and floats are JSON numbers, strings are JSON strings
+//! - terms are `{"functor": "f", "args": [...]}`, with `"annotations"` if
+//!   there are any and `"strong_negation": true` for `~f`
+//! - proper lists are arrays, and lists with an open tail are
+//!   `{"list": [...], "tail": ...}`
+//! - variables are `{"var": "Name"}`. A [`Value`] does not keep source
+//!   names, so [`value_to_json`] writes `_V<id>` instead, and
+//!   [`value_from_json`] gives equal names the same variable
+//! - unevaluated operators are `{"op": "+", "args": [...]}`, with one
+//!   argument for unary operators
+
+use std::fmt;
+
+use serde_json::{json, Map, Number};
+
+use crate::{
+    pretty::{binary_symbol, unary_symbol},
+    runtime::{BinaryOperator, Interner, List, UnaryOparator, Value},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvError {
+    /// A JSON value that does not match the schema, like `null` or an object
+    /// without a `functor`, `var`, `op` or `list` key.
+    Unexpected(serde_json::Value),
+    /// A number that is neither an `i64` nor a finite `f64`.
+    InvalidNumber(Number),
+    UnknownOperator(String),
+}
+
+impl fmt::Display for ConvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvError::Unexpected(value) => write!(f, "unexpected JSON value {}", value),
+            ConvError::InvalidNumber(number) => write!(f, "invalid number {}", number),
+            ConvError::UnknownOperator(op) => write!(f, "unknown operator {:?}", op),
+        }
+    }
+}
+
+impl std::error::Error for ConvError {}
+
+const UNARY_OPERATORS: [UnaryOparator; 3] =
+    [UnaryOparator::Pos, UnaryOparator::Neg, UnaryOparator::Not];
+
+const BINARY_OPERATORS: [BinaryOperator; 19] = [
+    BinaryOperator::Or,
+    BinaryOperator::And,
+    BinaryOperator::LtEq,
+    BinaryOperator::GtEq,
+    BinaryOperator::NotEqual,
+    BinaryOperator::Equal,
+    BinaryOperator::Decompose,
+    BinaryOperator::ArithEqual,
+    BinaryOperator::ArithNotEqual,
+    BinaryOperator::Eq,
+    BinaryOperator::Lt,
+    BinaryOperator::Gt,
+    BinaryOperator::Plus,
+    BinaryOperator::Minus,
+    BinaryOperator::Mul,
+    BinaryOperator::Div,
+    BinaryOperator::FloorDiv,
+    BinaryOperator::Mod,
+    BinaryOperator::Pow,
+];
+
+/// Converts a value to JSON. Variables are named `_V<id>`, as in the pretty
+/// printer. JSON has no representation for NaN and infinite floats, so they
+/// become `null`.
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Integer(n) => json!(n),
+        Value::Float(n) => Number::from_f64(*n).map_or(serde_json::Value::Null, Into::into),
+        Value::String(s) => json!(s),
+        Value::Term {
+            strong_negation,
+            functor,
+            args,
+            annotations,
+        } => {
+            let mut object = Map::new();
+            object.insert("functor".to_owned(), json!(functor));
+            object.insert("args".to_owned(), values_to_json(args));
+            if !annotations.is_empty() {
+                object.insert("annotations".to_owned(), values_to_json(annotations));
+            }
+            if *strong_negation {
+                object.insert("strong_negation".to_owned(), json!(true));
+            }
+            object.into()
+        }
+        Value::List(list) => {
+            let elements = serde_json::Value::Array(list.iter().map(value_to_json).collect());
+            match list.last_tail() {
+                Some(tail) => json!({ "list": elements, "tail": value_to_json(tail) }),
+                None => elements,
+            }
+        }
+        Value::Variable(id) => json!({ "var": format!("_V{}", id.0) }),
+        Value::UnaryOp { op, value } => json!({
+            "op": unary_symbol(*op).trim_end(),
+            "args": [value_to_json(value)],
+        }),
+        Value::BinaryOp { op, left, right } => json!({
+            "op": binary_symbol(*op),
+            "args": [value_to_json(left), value_to_json(right)],
+        }),
+    }
+}
+
+fn values_to_json(values: &[Value]) -> serde_json::Value {
+    values.iter().map(value_to_json).collect()
+}
+
+/// Converts JSON in the schema described in the [module docs](self) to a
+/// value. JSON booleans become the atoms `true` and `false`. Each call uses
+/// a fresh [`Interner`], so variable ids are only consistent within one
+/// value, as with [`Value::parse`].
+pub fn value_from_json(json: &serde_json::Value) -> Result<Value, ConvError> {
+    Converter::default().value(json)
+}
+
+#[derive(Default)]
+struct Converter {
+    interner: Interner,
+}
+
+impl Converter {
+    fn value(&mut self, json: &serde_json::Value) -> Result<Value, ConvError> {
+        let unexpected = || ConvError::Unexpected(json.clone());
+        Ok(match json {
+            serde_json::Value::Null => return Err(unexpected()),
+            serde_json::Value::Bool(b) => Value::atom(b.to_string()),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(n), _) => Value::Integer(n),
+                (None, Some(f)) if f.is_finite() && !n.is_u64() => Value::Float(f),
+                _ => return Err(ConvError::InvalidNumber(n.clone())),
+            },
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(elements) => {
+                Value::List(Box::new(List::new(self.values(elements)?, List::Empty)))
+            }
+            serde_json::Value::Object(object) => {
+                if let Some(functor) = object.get("functor") {
+                    let functor = functor.as_str().ok_or_else(unexpected)?;
+                    let args = match object.get("args") {
+                        Some(args) => self.array(args)?,
+                        None => Vec::new(),
+                    };
+                    let annotations = match object.get("annotations") {
+                        Some(annotations) => self.array(annotations)?,
+                        None => Vec::new(),
+                    };
+                    let strong_negation = match object.get("strong_negation") {
+                        Some(negated) => negated.as_bool().ok_or_else(unexpected)?,
+                        None => false,
+                    };
+                    Value::Term {
+                        strong_negation,
+                        functor: functor.to_owned(),
+                        args,
+                        annotations,
+                    }
+                } else if let Some(name) = object.get("var") {
+                    let name = name.as_str().ok_or_else(unexpected)?;
+                    Value::Variable(self.interner.variable(name))
+                } else if let Some(elements) = object.get("list") {
+                    let elements = self.array(elements)?;
+                    let tail = match object.get("tail") {
+                        Some(tail) => List::Tail(self.value(tail)?),
+                        None => List::Empty,
+                    };
+                    Value::List(Box::new(List::new(elements, tail)))
+                } else if let Some(op) = object.get("op") {
+                    let op = op.as_str().ok_or_else(unexpected)?;
+                    let mut args = self.array(object.get("args").ok_or_else(unexpected)?)?;
+                    match args.len() {
+                        1 => Value::UnaryOp {
+                            op: UNARY_OPERATORS
+                                .into_iter()
+                                .find(|candidate| unary_symbol(*candidate).trim_end() == op)
+                                .ok_or_else(|| ConvError::UnknownOperator(op.to_owned()))?,
+                            value: Box::new(args.remove(0)),
+                        },
+                        2 => {
+                            let right = args.remove(1);
+                            Value::BinaryOp {
+                                op: BINARY_OPERATORS
+                                    .into_iter()
+                                    .find(|candidate| binary_symbol(*candidate) == op)
+                                    .ok_or_else(|| ConvError::UnknownOperator(op.to_owned()))?,
+                                left: Box::new(args.remove(0)),
+                                right: Box::new(right),
+                            }
+                        }
+                        _ => return Err(unexpected()),
+                    }
+                } else {
+                    return Err(unexpected());
+                }
+            }
+        })
+    }
+
+    fn values(&mut self, values: &[serde_json::Value]) -> Result<Vec<Value>, ConvError> {
+        values.iter().map(|value| self.value(value)).collect()
+    }
+
+    fn array(&mut self, json: &serde_json::Value) -> Result<Vec<Value>, ConvError> {
+        match json {
+            serde_json::Value::Array(values) => self.values(values),
+            _ => Err(ConvError::Unexpected(json.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(source: &str) -> serde_json::Value {
+        let value = Value::parse(source).unwrap();
+        let json = value_to_json(&value);
+        assert_eq!(value_from_json(&json), Ok(value), "{}", json);
+        json
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(
+            round_trip("~at(home, [1, 2.5, \"x\"])[source(percept), seen(at(X, Y), X)]"),
+            json!({
+                "functor": "at",
+                "args": [
+                    { "functor": "home", "args": [] },
+                    [1, 2.5, "x"],
+                ],
+                "annotations": [
+                    { "functor": "source", "args": [{ "functor": "percept", "args": [] }] },
+                    {
+                        "functor": "seen",
+                        "args": [
+                            { "functor": "at", "args": [{ "var": "_V0" }, { "var": "_V1" }] },
+                            { "var": "_V0" },
+                        ],
+                    },
+                ],
+                "strong_negation": true,
+            })
+        );
+        assert_eq!(
+            round_trip("[a, b | T]"),
+            json!({
+                "list": [{ "functor": "a", "args": [] }, { "functor": "b", "args": [] }],
+                "tail": { "var": "_V0" },
+            })
+        );
+        assert_eq!(
+            round_trip("-(X + 1) * 2.0"),
+            json!({
+                "op": "*",
+                "args": [
+                    { "op": "-", "args": [{ "op": "+", "args": [{ "var": "_V0" }, 1] }] },
+                    2.0,
+                ],
+            })
+        );
+        round_trip("f(not g, 1 div 2, [])");
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert_eq!(
+            value_from_json(&json!({ "functor": "p", "args": [{ "var": "X" }, { "var": "X" }] })),
+            Ok(Value::parse("p(X, X)").unwrap())
+        );
+        assert_eq!(value_from_json(&json!(true)), Ok(Value::atom("true")));
+        assert_eq!(
+            value_from_json(&json!({ "functor": "a" })),
+            Ok(Value::atom("a"))
+        );
+
+        assert_eq!(
+            value_from_json(&json!([1, null])),
+            Err(ConvError::Unexpected(serde_json::Value::Null))
+        );
+        assert!(matches!(
+            value_from_json(&json!(u64::MAX)),
+            Err(ConvError::InvalidNumber(_))
+        ));
+        assert_eq!(
+            value_from_json(&json!({ "op": "<>", "args": [1, 2] })),
+            Err(ConvError::UnknownOperator("<>".to_owned()))
+        );
+        assert!(matches!(
+            value_from_json(&json!({ "functor": "f", "args": "x" })),
+            Err(ConvError::Unexpected(_))
+        ));
+
+        assert_eq!(
+            value_to_json(&Value::Float(f64::NAN)),
+            serde_json::Value::Null
+        );
+    }
+}
@@ -14,6 +14,8 @@ pub enum TokenKind {
     LineComment,
     /// `/* comment */`
     BlockComment { terminated: bool },
+    /// `#!/usr/bin/env pheres`, only at the very start of the input
+    Shebang,
 
     /// `foo`
     Functor,
@@ -122,12 +124,20 @@ pub enum TokenKind {
     Equal,
     /// `=..`
     Decompose,
+    /// `=:=`
+    ArithEqual,
+    /// `=\=`
+    ArithNotEqual,
     /// `=`
     Eq,
     /// `<`
     Lt,
     /// `>`
     Gt,
+    /// `<<`, not supported but lexed for better diagnostics
+    ShiftLeft,
+    /// `>>`, not supported but lexed for better diagnostics
+    ShiftRight,
 
     /// `;`
     Semi,
@@ -142,28 +152,89 @@ pub enum TokenKind {
     Unknown,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct LexerOptions {
+    /// Accept Unicode identifiers, as defined by XID_Start and XID_Continue.
+    /// Variables start with an uppercase letter and functors with any other
+    /// identifier start, including letters without case. Off by default,
+    /// which restricts identifiers to ASCII. Requires the `unicode-idents`
+    /// feature.
+    #[cfg(feature = "unicode-idents")]
+    pub unicode_idents: bool,
+}
+
 pub fn tokenize(input: &str) -> impl Iterator<Item = Token> + '_ {
-    let mut cursor = Cursor::new(input);
-    iter::from_fn(move || {
+    tokenize_with_options(input, &LexerOptions::default())
+}
+
+pub fn tokenize_with_options<'a>(
+    input: &'a str,
+    options: &LexerOptions,
+) -> impl Iterator<Item = Token> + 'a {
+    let shebang = shebang_len(input);
+    let mut cursor = Cursor::new(&input[shebang.unwrap_or(0)..], options.clone());
+    let shebang = shebang.map(|len| Token {
+        kind: TokenKind::Shebang,
+        len,
+    });
+    shebang.into_iter().chain(iter::from_fn(move || {
         if cursor.is_eof() {
             None
         } else {
             cursor.reset_len_consumed();
             Some(cursor.advance_token())
         }
+    }))
+}
+
+/// Length of the `#!` line at the start of the input, not including the
+/// line break, which may be `\r\n`.
+fn shebang_len(input: &str) -> Option<usize> {
+    input.starts_with("#!").then(|| {
+        let line = &input[..input.find('\n').unwrap_or(input.len())];
+        line.strip_suffix('\r').unwrap_or(line).len()
     })
 }
 
+#[cfg_attr(not(feature = "unicode-idents"), allow(unused_variables))]
+fn is_functor_start(ch: char, options: &LexerOptions) -> bool {
+    #[cfg(feature = "unicode-idents")]
+    if options.unicode_idents {
+        return unicode_ident::is_xid_start(ch) && !ch.is_uppercase();
+    }
+    ch.is_ascii_lowercase()
+}
+
+#[cfg_attr(not(feature = "unicode-idents"), allow(unused_variables))]
+fn is_variable_start(ch: char, options: &LexerOptions) -> bool {
+    #[cfg(feature = "unicode-idents")]
+    if options.unicode_idents {
+        return unicode_ident::is_xid_start(ch) && ch.is_uppercase();
+    }
+    ch.is_ascii_uppercase()
+}
+
+#[cfg_attr(not(feature = "unicode-idents"), allow(unused_variables))]
+fn is_ident_continue(ch: char, options: &LexerOptions) -> bool {
+    #[cfg(feature = "unicode-idents")]
+    if options.unicode_idents {
+        return unicode_ident::is_xid_continue(ch);
+    }
+    ch == '_' || ch.is_ascii_alphanumeric()
+}
+
 struct Cursor<'a> {
     initial_len: usize,
     chars: Chars<'a>,
+    options: LexerOptions,
 }
 
 impl Cursor<'_> {
-    pub fn new(input: &str) -> Cursor<'_> {
+    pub fn new(input: &str, options: LexerOptions) -> Cursor<'_> {
         Cursor {
             initial_len: input.len(),
             chars: input.chars(),
+            options,
         }
     }
 
@@ -215,6 +286,23 @@ impl Cursor<'_> {
         }
     }
 
+    /// Like [`Cursor::followed_by`], but only matches if the keyword is not
+    /// just the prefix of a longer identifier, like `true` in `truer`.
+    fn followed_by_keyword(&mut self, s: &str) -> bool {
+        let rest = self.chars.as_str();
+        if rest.starts_with(s)
+            && !rest[s.len()..]
+                .chars()
+                .next()
+                .is_some_and(|ch| is_ident_continue(ch, &self.options))
+        {
+            self.chars = rest[s.len()..].chars();
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn advance_token(&mut self) -> Token {
         Token {
             kind: match self.bump().unwrap() {
@@ -264,6 +352,10 @@ impl Cursor<'_> {
                         self.bump();
                         TokenKind::LtEq
                     }
+                    '<' => {
+                        self.bump();
+                        TokenKind::ShiftLeft
+                    }
                     _ => TokenKind::Lt,
                 },
                 '>' => match self.first() {
@@ -271,6 +363,10 @@ impl Cursor<'_> {
                         self.bump();
                         TokenKind::GtEq
                     }
+                    '>' => {
+                        self.bump();
+                        TokenKind::ShiftRight
+                    }
                     _ => TokenKind::Gt,
                 },
                 '=' => match (self.first(), self.second()) {
@@ -283,6 +379,16 @@ impl Cursor<'_> {
                         self.bump();
                         TokenKind::Decompose
                     }
+                    (':', '=') => {
+                        self.bump();
+                        self.bump();
+                        TokenKind::ArithEqual
+                    }
+                    ('\\', '=') => {
+                        self.bump();
+                        self.bump();
+                        TokenKind::ArithNotEqual
+                    }
                     _ => TokenKind::Eq,
                 },
                 '*' => match self.first() {
@@ -316,7 +422,7 @@ impl Cursor<'_> {
                 '+' => TokenKind::Plus,
                 '~' => TokenKind::Tilde,
                 '.' => {
-                    if self.first().is_ascii_lowercase() {
+                    if is_functor_start(self.first(), &self.options) {
                         self.bump();
                         self.functor()
                     } else {
@@ -327,25 +433,26 @@ impl Cursor<'_> {
                 ';' => TokenKind::Semi,
                 '@' => TokenKind::At,
                 '\\' if self.followed_by("==") => TokenKind::NotEqual,
-                't' if self.followed_by("rue") => TokenKind::True,
-                'f' if self.followed_by("alse") => TokenKind::False,
-                'i' if self.followed_by("f") => TokenKind::If,
-                'e' if self.followed_by("lse") => TokenKind::Else,
-                'e' if self.followed_by("lif") => TokenKind::Elif,
-                'w' if self.followed_by("hile") => TokenKind::While,
-                'f' if self.followed_by("or") => TokenKind::For,
-                'i' if self.followed_by("nclude") => TokenKind::Include,
-                'b' if self.followed_by("egin") => TokenKind::Begin,
-                'e' if self.followed_by("nd") => TokenKind::End,
-                'n' if self.followed_by("ot") => TokenKind::Not,
-                'd' if self.followed_by("iv") => TokenKind::Div,
-                'm' if self.followed_by("od") => TokenKind::Mod,
-                ch if ch.is_ascii_uppercase() => self.variable(),
-                ch if ch.is_ascii_lowercase() => self.functor(),
+                't' if self.followed_by_keyword("rue") => TokenKind::True,
+                'f' if self.followed_by_keyword("alse") => TokenKind::False,
+                'i' if self.followed_by_keyword("f") => TokenKind::If,
+                'e' if self.followed_by_keyword("lse") => TokenKind::Else,
+                'e' if self.followed_by_keyword("lif") => TokenKind::Elif,
+                'w' if self.followed_by_keyword("hile") => TokenKind::While,
+                'f' if self.followed_by_keyword("or") => TokenKind::For,
+                'i' if self.followed_by_keyword("nclude") => TokenKind::Include,
+                'b' if self.followed_by_keyword("egin") => TokenKind::Begin,
+                'e' if self.followed_by_keyword("nd") => TokenKind::End,
+                'n' if self.followed_by_keyword("ot") => TokenKind::Not,
+                'd' if self.followed_by_keyword("iv") => TokenKind::Div,
+                'm' if self.followed_by_keyword("od") => TokenKind::Mod,
+                ch if is_variable_start(ch, &self.options) => self.variable(),
+                ch if is_functor_start(ch, &self.options) => self.functor(),
+                '0' if matches!(self.first(), 'x' | 'o' | 'b') => self.radix_integer(),
                 ch if ch.is_ascii_digit() => self.number(),
                 '_' => {
                     self.eat_while(|ch| ch == '_');
-                    if self.first().is_ascii_uppercase() {
+                    if is_variable_start(self.first(), &self.options) {
                         self.bump();
                         self.variable()
                     } else {
@@ -380,14 +487,16 @@ impl Cursor<'_> {
     }
 
     fn variable(&mut self) -> TokenKind {
-        self.eat_while(|ch| ch == '_' || ch.is_ascii_alphanumeric());
+        let options = self.options.clone();
+        self.eat_while(|ch| is_ident_continue(ch, &options));
         TokenKind::Variable
     }
 
     fn functor(&mut self) -> TokenKind {
+        let options = self.options.clone();
         loop {
-            self.eat_while(|ch| ch == '_' || ch.is_ascii_alphanumeric());
-            if self.first() == '.' && self.second().is_ascii_lowercase() {
+            self.eat_while(|ch| is_ident_continue(ch, &options));
+            if self.first() == '.' && is_functor_start(self.second(), &options) {
                 self.bump();
                 self.bump();
                 continue;
@@ -414,13 +523,47 @@ impl Cursor<'_> {
         TokenKind::String { terminated: false }
     }
 
+    /// `0x`, `0o` or `0b` followed by digits of that base. Trailing
+    /// identifier characters are consumed too, so that `0xGG` or `0b12` is a
+    /// single invalid token. A misplaced separator ends the literal, as in
+    /// decimal numbers.
+    fn radix_integer(&mut self) -> TokenKind {
+        let radix = match self.bump() {
+            Some('x') => 16,
+            Some('o') => 8,
+            _ => 2,
+        };
+        let leading_digit = self.first().is_digit(radix);
+        self.digits(radix);
+        let options = self.options.clone();
+        if !leading_digit || (self.first() != '_' && is_ident_continue(self.first(), &options)) {
+            self.eat_while(|ch| is_ident_continue(ch, &options));
+            TokenKind::Unknown
+        } else {
+            TokenKind::Integer
+        }
+    }
+
+    /// Digits of the given radix, with single `_` separators between them.
+    /// Stops before a leading, trailing or doubled separator.
+    fn digits(&mut self, radix: u32) {
+        loop {
+            self.eat_while(|ch| ch.is_digit(radix));
+            if self.first() == '_' && self.second().is_digit(radix) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
     fn number(&mut self) -> TokenKind {
         let mut kind = TokenKind::Integer;
-        self.eat_while(|ch| ch.is_ascii_digit());
+        self.digits(10);
         if self.first() == '.' && self.second().is_ascii_digit() {
             self.bump();
             self.bump();
-            self.eat_while(|ch| ch.is_ascii_digit());
+            self.digits(10);
             kind = TokenKind::Float;
         }
         match (self.first(), self.second(), self.third()) {
@@ -428,13 +571,13 @@ impl Cursor<'_> {
                 self.bump();
                 self.bump();
                 self.bump();
-                self.eat_while(|ch| ch.is_ascii_digit());
+                self.digits(10);
                 kind = TokenKind::Float;
             }
             ('e' | 'E', ch, _) if ch.is_ascii_digit() => {
                 self.bump();
                 self.bump();
-                self.eat_while(|ch| ch.is_ascii_digit());
+                self.digits(10);
                 kind = TokenKind::Float;
             }
             (_, _, _) => (),
@@ -447,6 +590,118 @@ impl Cursor<'_> {
 mod tests {
     use super::*;
 
+    fn tokens(input: &str) -> Vec<(TokenKind, usize)> {
+        tokenize(input)
+            .map(|token| (token.kind, token.len))
+            .collect()
+    }
+
+    /// Asserts that `input` lexes to exactly the given `(kind, len)` pairs.
+    macro_rules! assert_lex {
+        ($input:expr, [$(($kind:expr, $len:expr)),* $(,)?]) => {
+            assert_eq!(tokens($input), [$(($kind, $len)),*], "input: {:?}", $input)
+        };
+    }
+
+    #[test]
+    fn test_token_kinds() {
+        use TokenKind::*;
+
+        assert_lex!("", []);
+        assert_lex!(" \t\n", [(Whitespace, 3)]);
+        assert_lex!("// c\nx", [(LineComment, 4), (Whitespace, 1), (Functor, 1)]);
+        assert_lex!("/* a */", [(BlockComment { terminated: true }, 7)]);
+        assert_lex!("/* a", [(BlockComment { terminated: false }, 4)]);
+        assert_lex!("foo_Bar1", [(Functor, 8)]);
+        assert_lex!("Foo_1", [(Variable, 5)]);
+        assert_lex!("_", [(Wildcard, 1)]);
+        assert_lex!("_X", [(Variable, 2)]);
+        assert_lex!(".print", [(Functor, 6)]);
+        assert_lex!("a.b.c", [(Functor, 5)]);
+        assert_lex!("ns::foo", [(Functor, 2), (ColonColon, 2), (Functor, 3)]);
+        assert_lex!("42", [(Integer, 2)]);
+        assert_lex!("4.2", [(Float, 3)]);
+        assert_lex!("4e-2", [(Float, 4)]);
+        assert_lex!("4.2E10", [(Float, 6)]);
+        assert_lex!("4.", [(Integer, 1), (Dot, 1)]);
+        assert_lex!("0x1f", [(Integer, 4)]);
+        assert_lex!("\"a\\\"b\"", [(String { terminated: true }, 6)]);
+        assert_lex!("\"ab", [(String { terminated: false }, 3)]);
+        assert_lex!("true false", [(True, 4), (Whitespace, 1), (False, 5)]);
+        assert_lex!("truery", [(Functor, 6)]);
+        assert_lex!(
+            "if else elif",
+            [
+                (If, 2),
+                (Whitespace, 1),
+                (Else, 4),
+                (Whitespace, 1),
+                (Elif, 4)
+            ]
+        );
+        assert_lex!("while for", [(While, 5), (Whitespace, 1), (For, 3)]);
+        assert_lex!("forall", [(Functor, 6)]);
+        assert_lex!(
+            "include begin end",
+            [
+                (Include, 7),
+                (Whitespace, 1),
+                (Begin, 5),
+                (Whitespace, 1),
+                (End, 3)
+            ]
+        );
+        assert_lex!("included", [(Functor, 8)]);
+        assert_lex!(
+            "()[]{}",
+            [
+                (OpenParen, 1),
+                (CloseParen, 1),
+                (OpenBracket, 1),
+                (CloseBracket, 1),
+                (OpenBrace, 1),
+                (CloseBrace, 1)
+            ]
+        );
+        assert_lex!("<-<:", [(Arrow, 2), (ColonArrow, 2)]);
+        assert_lex!(":-:", [(Define, 2), (Colon, 1)]);
+        assert_lex!("|&||||", [(ForkJoinAnd, 3), (ForkJoinXor, 3)]);
+        assert_lex!(
+            "!!!?-+",
+            [(BangBang, 2), (Bang, 1), (Question, 1), (MinusPlus, 2)]
+        );
+        assert_lex!("not~", [(Not, 3), (Tilde, 1)]);
+        assert_lex!("+-*/", [(Plus, 1), (Minus, 1), (Star, 1), (Slash, 1)]);
+        assert_lex!(
+            "div mod **",
+            [
+                (Div, 3),
+                (Whitespace, 1),
+                (Mod, 3),
+                (Whitespace, 1),
+                (Pow, 2)
+            ]
+        );
+        assert_lex!(
+            "division modular",
+            [(Functor, 8), (Whitespace, 1), (Functor, 7)]
+        );
+        assert_lex!("&|", [(And, 1), (Or, 1)]);
+        assert_lex!(
+            "<=>=\\====",
+            [(LtEq, 2), (GtEq, 2), (NotEqual, 3), (Equal, 2)]
+        );
+        assert_lex!(
+            "=..=:==\\=",
+            [(Decompose, 3), (ArithEqual, 3), (ArithNotEqual, 3)]
+        );
+        assert_lex!("=<>", [(Eq, 1), (Lt, 1), (Gt, 1)]);
+        assert_lex!("<<>>", [(ShiftLeft, 2), (ShiftRight, 2)]);
+        assert_lex!(";,.@", [(Semi, 1), (Comma, 1), (Dot, 1), (At, 1)]);
+        assert_lex!("# c", [(LineComment, 3)]);
+        assert_lex!("$", [(Unknown, 1)]);
+    }
+
     #[test]
     fn test_tokenize() {
         let tokens: Vec<_> = tokenize("+!plan(X).").collect();
@@ -484,4 +739,409 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_define_colon() {
+        assert_eq!(
+            tokens("a:-b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Define, 2),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a: -b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Colon, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Minus, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a :- b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Define, 2),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a:b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Colon, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_star_slash() {
+        assert_eq!(
+            tokens("a*/b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Star, 1),
+                (TokenKind::Slash, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("2**3"),
+            [
+                (TokenKind::Integer, 1),
+                (TokenKind::Pow, 2),
+                (TokenKind::Integer, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a* *b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Star, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Star, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a***b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Pow, 2),
+                (TokenKind::Star, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("x/*comment*/y"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::BlockComment { terminated: true }, 11),
+                (TokenKind::Functor, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("a/*b"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::BlockComment { terminated: false }, 3),
+            ]
+        );
+        assert_eq!(
+            tokens("a//b\nc"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::LineComment, 3),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Functor, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dots() {
+        assert_eq!(
+            tokens("foo."),
+            [(TokenKind::Functor, 3), (TokenKind::Dot, 1)]
+        );
+        assert_eq!(
+            tokens("foo. bar"),
+            [
+                (TokenKind::Functor, 3),
+                (TokenKind::Dot, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Functor, 3),
+            ]
+        );
+        assert_eq!(tokens(".foo"), [(TokenKind::Functor, 4)]);
+        assert_eq!(
+            tokens(".print(X)."),
+            [
+                (TokenKind::Functor, 6),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Variable, 1),
+                (TokenKind::CloseParen, 1),
+                (TokenKind::Dot, 1),
+            ]
+        );
+        assert_eq!(tokens("foo.bar.baz"), [(TokenKind::Functor, 11)]);
+        assert_eq!(
+            tokens("foo.Bar"),
+            [
+                (TokenKind::Functor, 3),
+                (TokenKind::Dot, 1),
+                (TokenKind::Variable, 3),
+            ]
+        );
+        assert_eq!(
+            tokens("foo.."),
+            [
+                (TokenKind::Functor, 3),
+                (TokenKind::Dot, 1),
+                (TokenKind::Dot, 1),
+            ]
+        );
+        assert_eq!(tokens("1."), [(TokenKind::Integer, 1), (TokenKind::Dot, 1)]);
+        assert_eq!(tokens("."), [(TokenKind::Dot, 1)]);
+    }
+
+    #[cfg(feature = "unicode-idents")]
+    #[test]
+    fn test_unicode_idents() {
+        let options = LexerOptions {
+            unicode_idents: true,
+        };
+        let unicode_tokens = |input| -> Vec<_> {
+            tokenize_with_options(input, &options)
+                .map(|token| (token.kind, token.len))
+                .collect()
+        };
+
+        assert_eq!(
+            unicode_tokens("λόγος(Дом, X_ы)"),
+            [
+                (TokenKind::Functor, "λόγος".len()),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Variable, "Дом".len()),
+                (TokenKind::Comma, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Variable, "X_ы".len()),
+                (TokenKind::CloseParen, 1),
+            ]
+        );
+        assert_eq!(unicode_tokens(".σ.τ"), [(TokenKind::Functor, ".σ.τ".len())]);
+
+        // Combining marks continue an identifier but cannot start one.
+        assert_eq!(
+            unicode_tokens("cafe\u{301}(E\u{301})"),
+            [
+                (TokenKind::Functor, "cafe\u{301}".len()),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Variable, "E\u{301}".len()),
+                (TokenKind::CloseParen, 1),
+            ]
+        );
+        assert_eq!(
+            unicode_tokens("\u{301}a"),
+            [
+                (TokenKind::Unknown, "\u{301}".len()),
+                (TokenKind::Functor, 1),
+            ]
+        );
+
+        // Letters without case start functors.
+        assert_eq!(unicode_tokens("名前"), [(TokenKind::Functor, "名前".len())]);
+
+        // Lowercase or alphabetic, but not XID.
+        assert_eq!(unicode_tokens("ⓐ"), [(TokenKind::Unknown, "ⓐ".len())]);
+        assert_eq!(
+            unicode_tokens("aⓐ €"),
+            [
+                (TokenKind::Functor, 1),
+                (TokenKind::Unknown, "ⓐ".len()),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Unknown, "€".len()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ascii_idents() {
+        assert_eq!(
+            tokens("λ(Д)"),
+            [
+                (TokenKind::Unknown, "λ".len()),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Unknown, "Д".len()),
+                (TokenKind::CloseParen, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(
+            tokens("#!/usr/bin/env pheres\n# comment\n!go."),
+            [
+                (TokenKind::Shebang, 21),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::LineComment, 9),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::Bang, 1),
+                (TokenKind::Functor, 2),
+                (TokenKind::Dot, 1),
+            ]
+        );
+        assert_eq!(tokens("#!pheres"), [(TokenKind::Shebang, 8)]);
+        assert_eq!(
+            tokens("#!pheres\r\n!go."),
+            [
+                (TokenKind::Shebang, 8),
+                (TokenKind::Whitespace, 2),
+                (TokenKind::Bang, 1),
+                (TokenKind::Functor, 2),
+                (TokenKind::Dot, 1),
+            ]
+        );
+        assert_eq!(
+            tokens("!go.\n#!not a shebang"),
+            [
+                (TokenKind::Bang, 1),
+                (TokenKind::Functor, 2),
+                (TokenKind::Dot, 1),
+                (TokenKind::Whitespace, 1),
+                (TokenKind::LineComment, 15),
+            ]
+        );
+        assert_eq!(
+            tokens(" #!x"),
+            [(TokenKind::Whitespace, 1), (TokenKind::LineComment, 3)]
+        );
+    }
+
+    #[test]
+    fn test_shift_operators() {
+        let kinds: Vec<_> = tokenize("X >> 2 << 1 > 0")
+            .map(|token| token.kind)
+            .filter(|kind| *kind != TokenKind::Whitespace)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Variable,
+                TokenKind::ShiftRight,
+                TokenKind::Integer,
+                TokenKind::ShiftLeft,
+                TokenKind::Integer,
+                TokenKind::Gt,
+                TokenKind::Integer,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arith_equality() {
+        let kinds: Vec<_> = tokenize("1 =:= 1.0 =\\= X == Y \\== Z = W")
+            .map(|token| token.kind)
+            .filter(|kind| *kind != TokenKind::Whitespace)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Integer,
+                TokenKind::ArithEqual,
+                TokenKind::Float,
+                TokenKind::ArithNotEqual,
+                TokenKind::Variable,
+                TokenKind::Equal,
+                TokenKind::Variable,
+                TokenKind::NotEqual,
+                TokenKind::Variable,
+                TokenKind::Eq,
+                TokenKind::Variable,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_boundaries() {
+        assert_eq!(tokens("true"), [(TokenKind::True, 4)]);
+        assert_eq!(tokens("true."), [(TokenKind::True, 4), (TokenKind::Dot, 1)]);
+        assert_eq!(
+            tokens("div(X)"),
+            [
+                (TokenKind::Div, 3),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Variable, 1),
+                (TokenKind::CloseParen, 1),
+            ]
+        );
+        for word in [
+            "truery", "division", "modular", "included", "forall", "iffy", "end_",
+        ] {
+            assert_eq!(tokens(word), [(TokenKind::Functor, word.len())], "{word}");
+        }
+    }
+
+    #[test]
+    fn test_radix_integers() {
+        for literal in [
+            "0xFF",
+            "0xdeadBEEF",
+            "0o17",
+            "0b1010",
+            "0x0",
+            "0xFF_FF",
+            "0b1010_1010",
+            "0o7_7_7",
+        ] {
+            assert_eq!(
+                tokens(literal),
+                [(TokenKind::Integer, literal.len())],
+                "{literal}"
+            );
+        }
+        for literal in ["0x", "0xGG", "0o8", "0b102", "0b", "0x_FF", "0b_"] {
+            assert_eq!(
+                tokens(literal),
+                [(TokenKind::Unknown, literal.len())],
+                "{literal}"
+            );
+        }
+        assert_eq!(
+            tokens("0xA+1"),
+            [
+                (TokenKind::Integer, 3),
+                (TokenKind::Plus, 1),
+                (TokenKind::Integer, 1),
+            ]
+        );
+        assert_eq!(tokens("0"), [(TokenKind::Integer, 1)]);
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        use TokenKind::*;
+
+        assert_lex!("1_000_000", [(Integer, 9)]);
+        assert_lex!("3.141_592", [(Float, 9)]);
+        assert_lex!("1_000e1_0", [(Float, 9)]);
+        assert_lex!("100_", [(Integer, 3), (Wildcard, 1)]);
+        assert_lex!("0xFF_", [(Integer, 4), (Wildcard, 1)]);
+        assert_lex!("0xF__F", [(Integer, 3), (Variable, 3)]);
+        assert_lex!("0b1_2", [(Integer, 3), (Wildcard, 1), (Integer, 1)]);
+        assert_lex!("1__0", [(Integer, 1), (Wildcard, 2), (Integer, 1)]);
+        assert_lex!("_1", [(Wildcard, 1), (Integer, 1)]);
+        assert_lex!(
+            "1._5",
+            [(Integer, 1), (Dot, 1), (Wildcard, 1), (Integer, 1)]
+        );
+    }
+
+    #[test]
+    fn test_long_tokens() {
+        let functor = "a".repeat(1 << 20);
+        assert_eq!(
+            tokens(&format!("{functor}(X)")),
+            [
+                (TokenKind::Functor, 1 << 20),
+                (TokenKind::OpenParen, 1),
+                (TokenKind::Variable, 1),
+                (TokenKind::CloseParen, 1),
+            ]
+        );
+
+        let digits = "9".repeat(1 << 20);
+        assert_eq!(
+            tokens(&format!("{digits}.5")),
+            [(TokenKind::Float, (1 << 20) + 2)]
+        );
+        assert_eq!(tokens(&digits), [(TokenKind::Integer, 1 << 20)]);
+    }
 }
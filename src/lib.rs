@@ -0,0 +1,18 @@
+pub mod actions;
+pub mod analysis;
+pub mod ast;
+pub mod diagnostics;
+pub mod error;
+pub mod eval;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod lexer;
+pub mod loader;
+pub mod lower;
+pub mod parser;
+pub mod pretty;
+pub mod runtime;
+pub mod syntax;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,75 @@
+//! Loading an agent file together with the files it includes.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{parser::parse, syntax::LexedStr};
+
+/// The text of one loaded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    /// The canonical path of the file.
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// Reads the file at `path` and, depth first, every file named by an
+/// `{ include("...") }` directive, resolved relative to the directory of
+/// the including file. Each file is loaded once, so include cycles end. The
+/// sources are not validated; parse each of them to report errors.
+pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<Source>> {
+    let mut sources = Vec::new();
+    load_into(path.as_ref(), &mut sources)?;
+    Ok(sources)
+}
+
+fn load_into(path: &Path, sources: &mut Vec<Source>) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    if sources.iter().any(|source| source.path == path) {
+        return Ok(());
+    }
+
+    let text = fs::read_to_string(&path)?;
+    let lexed = LexedStr::new(&text);
+    let includes: Vec<String> = parse(&lexed)
+        .root()
+        .directives()
+        .filter_map(|directive| directive.include_path())
+        .collect();
+    sources.push(Source {
+        path: path.clone(),
+        text,
+    });
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for include in includes {
+        load_into(&dir.join(include), sources)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/include");
+        let sources = load(dir.join("main.asl")).unwrap();
+        let paths: Vec<PathBuf> = sources.into_iter().map(|source| source.path).collect();
+        assert_eq!(
+            paths,
+            [
+                dir.join("main.asl").canonicalize().unwrap(),
+                dir.join("lib/common.asl").canonicalize().unwrap(),
+            ]
+        );
+
+        assert_eq!(
+            load(dir.join("missing.asl")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
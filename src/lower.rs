@@ -0,0 +1,442 @@
+use std::{collections::HashMap, fmt};
+
+use rowan::TextRange;
+
+use crate::{
+    ast::{self, AstNode, Comparison, Literal, PlanContext, Term},
+    runtime::{BinaryOperator, Formula, Interner, List, UnaryOparator, Value},
+    syntax::{
+        trimmed_range, unescape, AdditiveOperator, ComparisonOperator, MultiplicativeOperator,
+        SyntaxKind, SyntaxNode, SyntaxToken, UnaryOperator,
+    },
+};
+
+#[derive(Debug)]
+pub enum LowerError {
+    InvalidNumber {
+        range: TextRange,
+    },
+    ChainedComparison {
+        range: TextRange,
+    },
+    Incomplete {
+        range: TextRange,
+    },
+    /// A construct that is parsed but has no runtime representation yet.
+    Unsupported {
+        kind: SyntaxKind,
+        range: TextRange,
+    },
+}
+
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            LowerError::InvalidNumber { .. } => "invalid number",
+            LowerError::ChainedComparison { .. } => "comparison operators cannot be chained",
+            LowerError::Incomplete { .. } => "incomplete term",
+            LowerError::Unsupported { .. } => "unsupported construct",
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct LowerOptions {
+    /// Rewrite chains like `1 < X < 5` into `1 < X & X < 5` instead of
+    /// rejecting them.
+    pub desugar_chained_comparisons: bool,
+}
+
+fn incomplete(node: &SyntaxNode) -> LowerError {
+    LowerError::Incomplete {
+        range: node.text_range(),
+    }
+}
+
+fn unsupported(node: &SyntaxNode) -> LowerError {
+    LowerError::Unsupported {
+        kind: node.kind(),
+        range: trimmed_range(node),
+    }
+}
+
+/// Source ranges of a lowered value and its subterms, keyed by path. A path
+/// lists child indices from the root: arguments then annotations of a
+/// literal, elements then the tail of a list, the operand of a unary
+/// operator, and left (0) and right (1) of a binary operator.
+#[derive(Debug, Default, Clone)]
+pub struct Spans {
+    ranges: HashMap<Vec<usize>, TextRange>,
+}
+
+impl Spans {
+    pub fn get(&self, path: &[usize]) -> Option<TextRange> {
+        self.ranges.get(path).copied()
+    }
+}
+
+pub fn lower_term(term: &Term, interner: &mut Interner) -> Result<Value, LowerError> {
+    lower_term_with_options(term, interner, &LowerOptions::default())
+}
+
+pub fn lower_term_with_options(
+    term: &Term,
+    interner: &mut Interner,
+    options: &LowerOptions,
+) -> Result<Value, LowerError> {
+    Lowerer {
+        interner,
+        options,
+        path: Vec::new(),
+        spans: None,
+    }
+    .term(term)
+}
+
+/// Like [`lower_term_with_options`], but also records where each subterm
+/// came from, so that errors found later can point back to the source.
+pub fn lower_term_with_spans(
+    term: &Term,
+    interner: &mut Interner,
+    options: &LowerOptions,
+) -> Result<(Value, Spans), LowerError> {
+    let mut lowerer = Lowerer {
+        interner,
+        options,
+        path: Vec::new(),
+        spans: Some(Spans::default()),
+    };
+    let value = lowerer.term(term)?;
+    Ok((value, lowerer.spans.unwrap_or_default()))
+}
+
+/// A lowered plan context, split into its conjuncts.
+#[derive(Debug, Default, Clone)]
+pub struct LoweredContext {
+    /// Literals, which are queries against the belief base that bind
+    /// variables.
+    pub goals: Vec<Value>,
+    /// Everything else, like comparisons, arithmetic and `not`, which is
+    /// tested with the bindings of the goals.
+    pub guards: Vec<Value>,
+}
+
+/// Lowers the conjuncts of a plan context, sharing variables through
+/// `interner`, and partitions them into goals and guards in source order.
+pub fn lower_context(
+    context: &PlanContext,
+    interner: &mut Interner,
+) -> Result<LoweredContext, LowerError> {
+    let term = context.term().ok_or_else(|| incomplete(context.syntax()))?;
+    let mut lowered = LoweredContext::default();
+    for conjunct in term.conjuncts() {
+        let value = lower_term(&conjunct, interner)?;
+        match conjunct {
+            Term::Literal(_) => lowered.goals.push(value),
+            _ => lowered.guards.push(value),
+        }
+    }
+    Ok(lowered)
+}
+
+/// Lowers a plan body formula. Prefixed formulas keep their operator as
+/// the kind, so `-+count(N)` is a single
+/// [`FormulaType::Replace`](crate::syntax::FormulaType::Replace) of
+/// `count(N)`, not a removal followed by an addition.
+pub fn lower_formula(
+    formula: &ast::Formula,
+    interner: &mut Interner,
+) -> Result<Formula, LowerError> {
+    let term = formula.term().ok_or_else(|| incomplete(formula.syntax()))?;
+    Ok(Formula {
+        kind: formula.kind(),
+        term: lower_term(&term, interner)?,
+    })
+}
+
+struct Lowerer<'a> {
+    interner: &'a mut Interner,
+    options: &'a LowerOptions,
+    path: Vec<usize>,
+    spans: Option<Spans>,
+}
+
+impl Lowerer<'_> {
+    fn record(&mut self, range: TextRange) {
+        if let Some(spans) = &mut self.spans {
+            spans.ranges.insert(self.path.clone(), range);
+        }
+    }
+
+    /// Copies the spans recorded under `from` to `to`, both relative to the
+    /// current path, for a value that appears twice.
+    fn copy_spans(&mut self, from: &[usize], to: &[usize]) {
+        let Some(spans) = &mut self.spans else {
+            return;
+        };
+        let from: Vec<usize> = self.path.iter().chain(from).copied().collect();
+        let to: Vec<usize> = self.path.iter().chain(to).copied().collect();
+        let copied: Vec<(Vec<usize>, TextRange)> = spans
+            .ranges
+            .iter()
+            .filter_map(|(path, range)| {
+                let suffix = path.strip_prefix(from.as_slice())?;
+                Some((to.iter().chain(suffix).copied().collect(), *range))
+            })
+            .collect();
+        spans.ranges.extend(copied);
+    }
+
+    fn child<T>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut Self) -> Result<T, LowerError>,
+    ) -> Result<T, LowerError> {
+        self.path.push(index);
+        let res = f(self);
+        self.path.pop();
+        res
+    }
+
+    fn term(&mut self, term: &Term) -> Result<Value, LowerError> {
+        self.record(term.text_range());
+        Ok(match term {
+            Term::Primitive(token) => self.primitive(token)?,
+            Term::Literal(literal) => self.literal(literal)?,
+            Term::List(list) => self.list(list)?,
+            Term::Disjunction(node) => {
+                self.binary_op(BinaryOperator::Or, node.syntax(), node.lhs(), node.rhs())?
+            }
+            Term::Conjunction(node) => {
+                self.binary_op(BinaryOperator::And, node.syntax(), node.lhs(), node.rhs())?
+            }
+            Term::Negation(node) => {
+                let operand = node.term().ok_or_else(|| incomplete(node.syntax()))?;
+                Value::UnaryOp {
+                    op: UnaryOparator::Not,
+                    value: Box::new(self.child(0, |l| l.term(&operand))?),
+                }
+            }
+            Term::Comparison(node) => self.comparison(node)?,
+            Term::ShiftExpression(node) => return Err(unsupported(node.syntax())),
+            Term::AdditiveExpression(node) => self.binary_op(
+                match node.op().ok_or_else(|| incomplete(node.syntax()))? {
+                    AdditiveOperator::Add => BinaryOperator::Plus,
+                    AdditiveOperator::Sub => BinaryOperator::Minus,
+                },
+                node.syntax(),
+                node.lhs(),
+                node.rhs(),
+            )?,
+            Term::MultiplicativeExpression(node) => self.binary_op(
+                match node.op().ok_or_else(|| incomplete(node.syntax()))? {
+                    MultiplicativeOperator::Mul => BinaryOperator::Mul,
+                    MultiplicativeOperator::Div => BinaryOperator::Div,
+                    MultiplicativeOperator::FloorDiv => BinaryOperator::FloorDiv,
+                    MultiplicativeOperator::Mod => BinaryOperator::Mod,
+                },
+                node.syntax(),
+                node.lhs(),
+                node.rhs(),
+            )?,
+            Term::UnaryExpression(node) => Value::UnaryOp {
+                op: match node.op().ok_or_else(|| incomplete(node.syntax()))? {
+                    UnaryOperator::Pos => UnaryOparator::Pos,
+                    UnaryOperator::Neg => UnaryOparator::Neg,
+                },
+                value: {
+                    let operand = node.operand().ok_or_else(|| incomplete(node.syntax()))?;
+                    Box::new(self.child(0, |l| l.term(&operand))?)
+                },
+            },
+            Term::Exponentiation(node) => {
+                self.binary_op(BinaryOperator::Pow, node.syntax(), node.lhs(), node.rhs())?
+            }
+        })
+    }
+
+    fn binary_op(
+        &mut self,
+        op: BinaryOperator,
+        node: &SyntaxNode,
+        lhs: Option<Term>,
+        rhs: Option<Term>,
+    ) -> Result<Value, LowerError> {
+        let left = lhs.ok_or_else(|| incomplete(node))?;
+        let right = rhs.ok_or_else(|| incomplete(node))?;
+        Ok(Value::BinaryOp {
+            op,
+            left: Box::new(self.child(0, |l| l.term(&left))?),
+            right: Box::new(self.child(1, |l| l.term(&right))?),
+        })
+    }
+
+    fn comparison(&mut self, node: &Comparison) -> Result<Value, LowerError> {
+        let op = match node.op().ok_or_else(|| incomplete(node.syntax()))? {
+            ComparisonOperator::LtEq => BinaryOperator::LtEq,
+            ComparisonOperator::GtEq => BinaryOperator::GtEq,
+            ComparisonOperator::NotEqual => BinaryOperator::NotEqual,
+            ComparisonOperator::Equal => BinaryOperator::Equal,
+            ComparisonOperator::Decompose => BinaryOperator::Decompose,
+            ComparisonOperator::ArithEqual => BinaryOperator::ArithEqual,
+            ComparisonOperator::ArithNotEqual => BinaryOperator::ArithNotEqual,
+            ComparisonOperator::Eq => BinaryOperator::Eq,
+            ComparisonOperator::Lt => BinaryOperator::Lt,
+            ComparisonOperator::Gt => BinaryOperator::Gt,
+        };
+
+        // Parentheses do not get their own node, so `(a < b) < c` is only
+        // told apart from a chain by the leading `(`.
+        let parenthesized = node
+            .syntax()
+            .children_with_tokens()
+            .find(|element| !element.kind().is_trivia())
+            .is_some_and(|element| element.kind() == SyntaxKind::OpenParen);
+
+        match node.lhs() {
+            Some(Term::Comparison(chain)) if !parenthesized => {
+                if !self.options.desugar_chained_comparisons {
+                    return Err(LowerError::ChainedComparison {
+                        range: node.syntax().text_range(),
+                    });
+                }
+
+                // a < b < c becomes a < b & b < c. The middle operand is
+                // lowered once, as part of the chain, and then copied along
+                // with its spans, so that wildcards stay the same variable.
+                let middle_term = chain.rhs().ok_or_else(|| incomplete(chain.syntax()))?;
+                let right = node.rhs().ok_or_else(|| incomplete(node.syntax()))?;
+                let left = self.child(0, |l| {
+                    l.record(trimmed_range(chain.syntax()));
+                    l.comparison(&chain)
+                })?;
+
+                // Longer chains lower to nested conjunctions, with the last
+                // comparison on the right.
+                let mut middle_path = vec![0];
+                let mut last = &left;
+                while let Value::BinaryOp {
+                    op: BinaryOperator::And,
+                    right,
+                    ..
+                } = last
+                {
+                    middle_path.push(1);
+                    last = right;
+                }
+                middle_path.push(1);
+                let middle = match last {
+                    Value::BinaryOp { right, .. } => (**right).clone(),
+                    _ => return Err(incomplete(chain.syntax())),
+                };
+                self.copy_spans(&middle_path, &[1, 0]);
+
+                let rest = self.child(1, |l| {
+                    l.record(TextRange::new(
+                        middle_term.text_range().start(),
+                        right.text_range().end(),
+                    ));
+                    Ok(Value::BinaryOp {
+                        op,
+                        left: Box::new(middle),
+                        right: Box::new(l.child(1, |l| l.term(&right))?),
+                    })
+                })?;
+                Ok(Value::BinaryOp {
+                    op: BinaryOperator::And,
+                    left: Box::new(left),
+                    right: Box::new(rest),
+                })
+            }
+            lhs => self.binary_op(op, node.syntax(), lhs, node.rhs()),
+        }
+    }
+
+    fn primitive(&mut self, token: &SyntaxToken) -> Result<Value, LowerError> {
+        let invalid_number = || LowerError::InvalidNumber {
+            range: token.text_range(),
+        };
+
+        Ok(match token.kind() {
+            SyntaxKind::Integer => Value::Integer(
+                parse_integer(&token.text().replace('_', "")).ok_or_else(invalid_number)?,
+            ),
+            SyntaxKind::Float => Value::Float(
+                token
+                    .text()
+                    .replace('_', "")
+                    .parse()
+                    .map_err(|_| invalid_number())?,
+            ),
+            SyntaxKind::String => Value::String(unescape(token.text())),
+            SyntaxKind::True => Value::atom("true"),
+            SyntaxKind::False => Value::atom("false"),
+            SyntaxKind::Variable => Value::Variable(self.interner.variable(token.text())),
+            SyntaxKind::Wildcard => Value::Variable(self.interner.fresh()),
+            kind => {
+                return Err(LowerError::Unsupported {
+                    kind,
+                    range: token.text_range(),
+                })
+            }
+        })
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Result<Value, LowerError> {
+        let functor = literal
+            .functor()
+            .ok_or_else(|| incomplete(literal.syntax()))?;
+
+        let args: Vec<Value> = match literal.terms() {
+            Some(terms) => terms
+                .terms()
+                .enumerate()
+                .map(|(i, term)| self.child(i, |l| l.term(&term)))
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        let annotations = match literal.annotations() {
+            Some(annotations) => annotations
+                .terms()
+                .enumerate()
+                .map(|(i, term)| self.child(args.len() + i, |l| l.term(&term)))
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Value::Term {
+            strong_negation: literal.is_strongly_negated(),
+            functor: functor.text().to_owned(),
+            args,
+            annotations,
+        })
+    }
+
+    fn list(&mut self, list: &ast::List) -> Result<Value, LowerError> {
+        let mut elements = Vec::new();
+        for term in list.elements() {
+            elements.push(self.child(elements.len(), |l| l.term(&term))?);
+        }
+
+        let tail = match list.tail() {
+            Some(tail) => {
+                let term = tail.term().ok_or_else(|| incomplete(tail.syntax()))?;
+                List::Tail(self.child(elements.len(), |l| l.term(&term))?)
+            }
+            None => List::Empty,
+        };
+        Ok(Value::List(Box::new(List::new(elements, tail))))
+    }
+}
+
+/// Parses a decimal integer or one with a `0x`, `0o` or `0b` prefix.
+fn parse_integer(text: &str) -> Option<i64> {
+    let (digits, radix) = match text.get(..2) {
+        Some("0x") => (&text[2..], 16),
+        Some("0o") => (&text[2..], 8),
+        Some("0b") => (&text[2..], 2),
+        _ => (text, 10),
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
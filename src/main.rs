@@ -1,93 +1,61 @@
 use codespan_reporting::{
-    diagnostic::{Diagnostic, Label},
-    files::SimpleFiles,
+    files::SimpleFile,
     term,
     term::termcolor::{ColorChoice, StandardStream},
 };
-use rowan::NodeOrToken;
-
-mod lexer;
-mod parser;
-mod syntax;
-mod runtime;
-
-use crate::{
+use pheres::{
+    diagnostics::{has_errors, lint_parsed, validate_parsed},
     parser::parse,
-    syntax::{LexedStr, SyntaxElement, SyntaxKind, SyntaxNode},
+    syntax::{debug_tree, sexpr_tree, token_table, LexedStr},
 };
 
-fn print(level: usize, element: SyntaxElement) {
-    let kind: SyntaxKind = element.kind().into();
-    print!("{:indent$}", "", indent = level * 2);
-    match element {
-        NodeOrToken::Node(node) => {
-            println!("- {:?}", kind);
-            for child in node.children_with_tokens() {
-                print(level + 1, child);
+fn main() {
+    let mut sexpr = false;
+    let mut tokens = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--tree-format=indent" => sexpr = false,
+            "--tree-format=sexpr" => sexpr = true,
+            "--tokens" => tokens = true,
+            _ => {
+                eprintln!("usage: pheres [--tokens] [--tree-format=sexpr|indent]");
+                std::process::exit(2);
             }
         }
-        NodeOrToken::Token(token) => println!("- {:?} {:?}", token.text(), kind),
     }
-}
-
-fn main() {
-    let mut files = SimpleFiles::new();
 
     let source = include_str!("../test.asl");
-    let file_id = files.add("test.asl", source);
-
-    let lexed = LexedStr::new(source);
+    let file = SimpleFile::new("test.asl", source);
 
     let diagnostic_stream = StandardStream::stderr(ColorChoice::Auto);
     let diagnostic_config = term::Config::default();
 
-    for error in &lexed.errors {
-        let diagnostic = Diagnostic::error()
-            .with_message(error.kind.to_string())
-            .with_labels(vec![Label::primary(
-                file_id,
-                lexed.token_range(error.token_idx),
-            )]);
-        term::emit(
-            &mut diagnostic_stream.lock(),
-            &diagnostic_config,
-            &files,
-            &diagnostic,
-        )
-        .unwrap();
-    }
-
+    let lexed = LexedStr::new(source);
     let parsed = parse(&lexed);
+    let root = parsed.syntax();
+
+    let mut diagnostics = validate_parsed(&lexed, &parsed);
+    diagnostics.extend(lint_parsed(&root));
 
-    for error in &parsed.errors {
-        let diagnostic = Diagnostic::error()
-            .with_message(error.to_string())
-            .with_labels(vec![Label::primary(
-                file_id,
-                lexed.token_range(error.token_idx),
-            )]);
+    for diagnostic in &diagnostics {
         term::emit(
             &mut diagnostic_stream.lock(),
             &diagnostic_config,
-            &files,
-            &diagnostic,
+            &file,
+            diagnostic,
         )
         .unwrap();
     }
 
-    if parsed.unexpected_eof {
-        let last = lexed.text.len() - 1;
-        let diagnostic = Diagnostic::error()
-            .with_message("unexpected end of file")
-            .with_labels(vec![Label::primary(file_id, last..last)]);
-        term::emit(
-            &mut diagnostic_stream.lock(),
-            &diagnostic_config,
-            &files,
-            &diagnostic,
-        )
-        .unwrap();
+    if tokens {
+        print!("{}", token_table(&lexed));
+    } else if sexpr {
+        println!("{}", sexpr_tree(&root));
+    } else {
+        print!("{}", debug_tree(&root));
     }
 
-    print(0, SyntaxNode::new_root(parsed.green_node).into());
+    if has_errors(&diagnostics) {
+        std::process::exit(1);
+    }
 }
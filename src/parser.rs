@@ -1,8 +1,12 @@
-use std::fmt;
+use std::{fmt, ops::Range};
 
-use rowan::{GreenNode, GreenNodeBuilder};
+use rowan::{Checkpoint, GreenNode, GreenNodeBuilder};
 
-use crate::syntax::{LexedStr, LexedStrIter, SyntaxKind, TokenIdx};
+use crate::{
+    ast::{self, AstNode},
+    error::Severity,
+    syntax::{LexedStr, LexedStrIter, SyntaxKind, SyntaxNode, TokenIdx},
+};
 
 #[derive(Debug)]
 pub struct Parsed {
@@ -11,10 +15,90 @@ pub struct Parsed {
     pub unexpected_eof: bool,
 }
 
+impl Parsed {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green_node.clone())
+    }
+
+    pub fn root(&self) -> ast::Root {
+        ast::Root::cast(self.syntax()).expect("parser always produces a root node")
+    }
+}
+
+/// Replacement of the byte range `delete` of the source with `insert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub delete: Range<usize>,
+    pub insert: String,
+}
+
+/// Byte range of the top-level items an edit of the parsed source touches,
+/// as a starting point for reparsing. Items that merely border the edit are
+/// included, since typing at the end of an item may extend it. An edit
+/// between items affects the gap up to the neighbouring items.
+pub fn affected_item_range(parsed: &Parsed, edit: &TextEdit) -> Range<usize> {
+    let root = parsed.syntax();
+    let items: Vec<Range<usize>> = root
+        .children()
+        .map(|node| {
+            let range = node.text_range();
+            range.start().into()..range.end().into()
+        })
+        .collect();
+
+    let touched = items
+        .iter()
+        .filter(|item| item.start <= edit.delete.end && edit.delete.start <= item.end);
+    let (start, end) = touched.fold(
+        (edit.delete.start, edit.delete.end),
+        |(start, end), item| (start.min(item.start), end.max(item.end)),
+    );
+    if start < edit.delete.start || end > edit.delete.end {
+        return start..end;
+    }
+
+    let before = items
+        .iter()
+        .rev()
+        .find(|item| item.end <= edit.delete.start)
+        .map_or(0, |item| item.end);
+    let after = items
+        .iter()
+        .find(|item| item.start >= edit.delete.end)
+        .map_or_else(|| root.text_range().end().into(), |item| item.start);
+    before..after
+}
+
+/// What went wrong, for tools that react to specific errors. The message
+/// has the details.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ParserErrorKind {
+    UnexpectedToken,
+    /// A rule, belief, initial goal or plan body is not terminated by `.`.
+    MissingDot,
+    ExpectedLiteral,
+    ExpectedTerm,
+    ExpectedTrigger,
+    ExpectedDirective,
+    /// A control flow construct without its parenthesized condition.
+    ExpectedCondition,
+    /// A control flow construct without its `{ ... }` body.
+    ExpectedBlock,
+    UnclosedParen,
+    UnclosedBracket,
+    UnclosedBrace,
+    TrailingComma,
+    /// Elements after the `|` of a list, like `[a | b, c]`.
+    InvalidListTail,
+    UnsupportedOperator,
+}
+
 #[derive(Debug)]
 pub struct ParserError {
+    pub kind: ParserErrorKind,
     pub message: String,
     pub token_idx: TokenIdx,
+    pub severity: Severity,
 }
 
 impl fmt::Display for ParserError {
@@ -40,13 +124,33 @@ pub fn parse(lexed: &LexedStr<'_>) -> Parsed {
     .parse()
 }
 
+/// Parses a token stream that was produced without the lexer, for example
+/// by another tool or by hand in tests. The token texts are concatenated to
+/// form the source of the tree.
+pub fn parse_tokens(tokens: Vec<(SyntaxKind, &str)>) -> Parsed {
+    let text: String = tokens.iter().map(|(_, text)| *text).collect();
+    let lexed = LexedStr::from_kinds(&text, tokens.iter().map(|(kind, text)| (*kind, text.len())));
+    parse(&lexed)
+}
+
+/// Parses a single standalone term, as used for queries and when building
+/// values from source text.
+pub fn parse_term(lexed: &LexedStr<'_>) -> Parsed {
+    Parser {
+        builder: GreenNodeBuilder::new(),
+        tokens: lexed.iter(),
+        errors: Vec::new(),
+        unexpected_eof: false,
+    }
+    .parse_standalone_term()
+}
+
 impl Parser<'_> {
     fn skip_noise(&mut self) {
-        while let Some((
-            SyntaxKind::Whitespace | SyntaxKind::LineComment | SyntaxKind::BlockComment,
-            _,
-        )) = self.tokens.peek()
-        {
+        while let Some((kind, _)) = self.tokens.peek() {
+            if !kind.is_trivia() {
+                break;
+            }
             self.bump();
         }
     }
@@ -65,20 +169,51 @@ impl Parser<'_> {
         self.builder.start_node(SyntaxKind::Root.into());
 
         while let Some(token) = self.current() {
-            match token {
-                SyntaxKind::Functor => self.parse_rule_or_belief(),
-                SyntaxKind::Bang => self.parse_initial_goal(),
-                SyntaxKind::At | SyntaxKind::Plus | SyntaxKind::Minus => self.parse_plan(),
-                _ => self.recover(
-                    format!("unexpected token {:?}", token),
-                    |t| t == SyntaxKind::Dot,
-                    |_| false,
-                ),
+            self.parse_item(token);
+        }
+
+        self.builder.finish_node(); // root
+
+        self.finish()
+    }
+
+    fn parse_item(&mut self, token: SyntaxKind) {
+        match token {
+            SyntaxKind::Functor | SyntaxKind::Tilde => self.parse_rule_or_belief(),
+            SyntaxKind::Bang => self.parse_initial_goal(),
+            SyntaxKind::OpenBrace => self.parse_directive(),
+            SyntaxKind::At | SyntaxKind::Plus | SyntaxKind::Minus | SyntaxKind::Begin => {
+                self.parse_plan()
             }
+            _ => self.recover(
+                ParserErrorKind::UnexpectedToken,
+                format!("unexpected token {:?}", token),
+                |t| t == SyntaxKind::Dot,
+                |_| false,
+            ),
+        }
+    }
+
+    fn parse_standalone_term(mut self) -> Parsed {
+        self.builder.start_node(SyntaxKind::Root.into());
+
+        self.parse_term();
+
+        if let Some(token) = self.current() {
+            self.recover(
+                ParserErrorKind::UnexpectedToken,
+                format!("unexpected token {:?} after term", token),
+                |_| false,
+                |_| false,
+            );
         }
 
         self.builder.finish_node(); // root
 
+        self.finish()
+    }
+
+    fn finish(self) -> Parsed {
         Parsed {
             green_node: self.builder.finish(),
             errors: self.errors,
@@ -104,6 +239,7 @@ impl Parser<'_> {
             self.bump();
         } else {
             self.recover(
+                ParserErrorKind::MissingDot,
                 "expected '.' after rule or belief",
                 |t| t == SyntaxKind::Dot,
                 |_| false,
@@ -123,6 +259,7 @@ impl Parser<'_> {
             Some(SyntaxKind::Functor) => self.parse_literal(),
             Some(token) => {
                 self.recover(
+                    ParserErrorKind::ExpectedLiteral,
                     format!("expected functor after '!', got {:?}", token),
                     |t| t == SyntaxKind::Dot,
                     |_| false,
@@ -140,6 +277,7 @@ impl Parser<'_> {
         match self.current() {
             Some(SyntaxKind::Dot) => self.bump(),
             Some(token) => self.recover(
+                ParserErrorKind::MissingDot,
                 format!("expected '.' after initial goal, got {:?}", token),
                 |t| t == SyntaxKind::Dot,
                 |_| false,
@@ -150,19 +288,89 @@ impl Parser<'_> {
         self.builder.finish_node();
     }
 
+    fn parse_directive(&mut self) {
+        self.builder.start_node(SyntaxKind::Directive.into());
+
+        assert!(self.current() == Some(SyntaxKind::OpenBrace));
+        self.bump();
+
+        match self.current() {
+            // Jason's `{ begin namespace(ns) }` takes a literal, and a bare
+            // `{ begin }` or `{ end }` is valid too.
+            Some(SyntaxKind::Begin) => {
+                self.bump();
+                if self.current() == Some(SyntaxKind::Functor) {
+                    self.parse_literal();
+                }
+            }
+            Some(SyntaxKind::Include | SyntaxKind::End | SyntaxKind::Functor) => {
+                self.bump();
+                if self.current() == Some(SyntaxKind::OpenParen) {
+                    self.parse_literal_terms();
+                }
+            }
+            Some(token) => {
+                self.recover(
+                    ParserErrorKind::ExpectedDirective,
+                    format!("expected directive, got {:?}", token),
+                    |t| t == SyntaxKind::CloseBrace,
+                    |_| false,
+                );
+                self.builder.finish_node();
+                return;
+            }
+            None => {
+                self.unexpected_eof = true;
+                self.builder.finish_node();
+                return;
+            }
+        }
+
+        match self.current() {
+            Some(SyntaxKind::CloseBrace) => self.bump(),
+            Some(token) => self.recover(
+                ParserErrorKind::UnclosedBrace,
+                format!("expected '}}' to close directive, got {:?}", token),
+                |t| t == SyntaxKind::CloseBrace,
+                |_| false,
+            ),
+            None => self.unexpected_eof = true,
+        }
+
+        self.builder.finish_node();
+    }
+
     fn parse_plan(&mut self) {
-        self.builder.start_node(SyntaxKind::Plan.into());
+        let checkpoint = self.builder.checkpoint();
 
         while self.current() == Some(SyntaxKind::At) {
             self.builder.start_node(SyntaxKind::PlanAnnotation.into());
             self.bump();
-            self.parse_literal();
+            if self.current() == Some(SyntaxKind::OpenBracket) {
+                self.parse_literal_annotations();
+            } else {
+                self.parse_literal();
+            }
             self.builder.finish_node();
         }
 
+        if self.current() == Some(SyntaxKind::Begin) {
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::PlanGroup.into());
+            self.parse_plan_group_items();
+            self.builder.finish_node();
+            return;
+        }
+
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::Plan.into());
+
         match self.current() {
             Some(SyntaxKind::Plus | SyntaxKind::Minus) => self.bump(),
-            _ => self.push_error("expected '+' or '-' for plan trigger"),
+            _ => self.push_error(
+                ParserErrorKind::ExpectedTrigger,
+                "expected '+' or '-' for plan trigger",
+            ),
         }
 
         if let Some(SyntaxKind::Bang) = self.current() {
@@ -181,36 +389,217 @@ impl Parser<'_> {
         if self.current() == Some(SyntaxKind::Arrow) {
             self.bump();
             self.builder.start_node(SyntaxKind::Body.into());
-            loop {
+            self.parse_body(SyntaxKind::Dot);
+            self.builder.finish_node();
+        }
+
+        self.builder.finish_node();
+    }
+
+    /// Parses `;`-separated formulas up to and including `end`, which is the
+    /// `.` of a plan or the `}` of a block.
+    fn parse_body(&mut self, end: SyntaxKind) {
+        let (kind, expected) = match end {
+            SyntaxKind::Dot => (ParserErrorKind::MissingDot, "expected ';' or '.'"),
+            _ => (ParserErrorKind::UnclosedBrace, "expected ';' or '}'"),
+        };
+
+        let mut recovered = false;
+        loop {
+            if !recovered {
                 self.parse_formula();
-                match self.current() {
-                    Some(SyntaxKind::Semi) => self.bump(),
-                    Some(SyntaxKind::Dot) => {
-                        self.bump();
-                        break;
-                    }
-                    Some(token) => self.recover(
-                        format!("expected ';' or '.', got {:?}", token),
+            }
+            recovered = false;
+            match self.current() {
+                Some(SyntaxKind::Semi) => self.bump(),
+                Some(token) if token == end => {
+                    self.bump();
+                    break;
+                }
+                Some(SyntaxKind::Dot) => {
+                    // Leave the `.` to end the enclosing plan.
+                    self.push_error(kind, format!("{}, got Dot", expected));
+                    break;
+                }
+                Some(token) => {
+                    self.recover(
+                        kind,
+                        format!("{}, got {:?}", expected, token),
                         |_| false,
-                        |t| t == SyntaxKind::Semi || t == SyntaxKind::Dot,
-                    ),
-                    None => {
-                        self.unexpected_eof = true;
-                        break;
-                    }
+                        |t| t == SyntaxKind::Semi || t == SyntaxKind::Dot || t == end,
+                    );
+                    recovered = true;
+                }
+                None => {
+                    self.unexpected_eof = true;
+                    break;
                 }
             }
-            self.builder.finish_node();
         }
+    }
 
+    /// Parses `{ ... }` as a nested body.
+    fn parse_block(&mut self) {
+        match self.current() {
+            Some(SyntaxKind::OpenBrace) => {
+                self.builder.start_node(SyntaxKind::Body.into());
+                self.bump();
+                if self.current() == Some(SyntaxKind::CloseBrace) {
+                    self.bump();
+                } else {
+                    self.parse_body(SyntaxKind::CloseBrace);
+                }
+                self.builder.finish_node();
+            }
+            Some(token) if SyntaxKind::Arrow.expected_after().contains(&token) => {
+                // Take a single formula as the body, like `while (c) a;`.
+                self.push_error(
+                    ParserErrorKind::ExpectedBlock,
+                    format!("expected '{{', got {:?}", token),
+                );
+                self.builder.start_node(SyntaxKind::Body.into());
+                self.parse_formula();
+                self.builder.finish_node();
+            }
+            Some(token) => {
+                // Skip stray tokens, like an extra `)`, up to the block.
+                self.recover(
+                    ParserErrorKind::ExpectedBlock,
+                    format!("expected '{{', got {:?}", token),
+                    |_| false,
+                    |t| {
+                        matches!(
+                            t,
+                            SyntaxKind::OpenBrace
+                                | SyntaxKind::CloseBrace
+                                | SyntaxKind::Semi
+                                | SyntaxKind::Dot
+                        )
+                    },
+                );
+                if self.current() == Some(SyntaxKind::OpenBrace) {
+                    self.parse_block();
+                }
+            }
+            None => self.unexpected_eof = true,
+        }
+    }
+
+    /// Parses the parenthesized condition of a control flow construct.
+    fn parse_condition(&mut self) {
+        match self.current() {
+            Some(SyntaxKind::OpenParen) => self.bump(),
+            Some(token) => {
+                // Parse the condition anyway, so that the block is found.
+                self.push_error(
+                    ParserErrorKind::ExpectedCondition,
+                    format!("expected '(', got {:?}", token),
+                );
+                self.parse_term();
+                return;
+            }
+            None => {
+                self.unexpected_eof = true;
+                return;
+            }
+        }
+        self.parse_term();
+        match self.current() {
+            Some(SyntaxKind::CloseParen) => self.bump(),
+            Some(token) => self.recover(
+                ParserErrorKind::UnclosedParen,
+                format!("expected ')', got {:?}", token),
+                |t| t == SyntaxKind::CloseParen,
+                |t| t == SyntaxKind::OpenBrace || t == SyntaxKind::Semi || t == SyntaxKind::Dot,
+            ),
+            None => self.unexpected_eof = true,
+        }
+    }
+
+    fn parse_while_loop(&mut self, checkpoint: Checkpoint) {
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::WhileLoop.into());
+        assert!(self.current() == Some(SyntaxKind::While));
+        self.bump();
+        self.parse_condition();
+        self.parse_block();
+        self.builder.finish_node();
+    }
+
+    fn parse_plan_group_items(&mut self) {
+        assert!(self.current() == Some(SyntaxKind::Begin));
+        self.bump();
+
+        loop {
+            match self.current() {
+                Some(SyntaxKind::End) => {
+                    self.bump();
+                    break;
+                }
+                Some(token) => self.parse_item(token),
+                None => {
+                    self.unexpected_eof = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Parses `for (generator) { ... }`, where the generator is a term like
+    /// `.member(X, L)`.
+    fn parse_for_loop(&mut self, checkpoint: Checkpoint) {
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::ForLoop.into());
+        assert!(self.current() == Some(SyntaxKind::For));
+        self.bump();
+        self.parse_condition();
+        self.parse_block();
+        self.builder.finish_node();
+    }
+
+    /// Parses `if (c) { ... }` with optional `else { ... }`. An `else if` or
+    /// `elif` nests another `IfThenElse` in place of the else block.
+    fn parse_if_then_else(&mut self, checkpoint: Checkpoint) {
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::IfThenElse.into());
+        assert!(matches!(
+            self.current(),
+            Some(SyntaxKind::If | SyntaxKind::Elif)
+        ));
+        self.bump();
+        self.parse_condition();
+        self.parse_block();
+
+        let checkpoint = self.builder.checkpoint();
+        match self.current() {
+            Some(SyntaxKind::Elif) => self.parse_if_then_else(checkpoint),
+            Some(SyntaxKind::Else) => {
+                self.bump();
+                let checkpoint = self.builder.checkpoint();
+                if self.current() == Some(SyntaxKind::If) {
+                    self.parse_if_then_else(checkpoint);
+                } else {
+                    self.parse_block();
+                }
+            }
+            _ => (),
+        }
         self.builder.finish_node();
     }
 
     fn parse_formula(&mut self) {
-        self.builder.start_node(SyntaxKind::Formula.into());
+        let checkpoint = self.builder.checkpoint();
+        match self.current() {
+            Some(SyntaxKind::While) => return self.parse_while_loop(checkpoint),
+            Some(SyntaxKind::If) => return self.parse_if_then_else(checkpoint),
+            Some(SyntaxKind::For) => return self.parse_for_loop(checkpoint),
+            _ => (),
+        }
+
+        self.builder
+            .start_node_at(checkpoint, SyntaxKind::Formula.into());
         match self.current() {
             Some(token) if token.formula_type().is_some() => self.bump(),
-            Some(SyntaxKind::While | SyntaxKind::If | SyntaxKind::For) => todo!("control flow"),
             Some(_) => (),
             None => self.unexpected_eof = true,
         }
@@ -221,10 +610,17 @@ impl Parser<'_> {
     fn parse_literal(&mut self) {
         self.builder.start_node(SyntaxKind::Literal.into());
 
+        // Strong negation, like `~colour(X)`.
+        if self.current() == Some(SyntaxKind::Tilde) {
+            self.bump();
+        }
+
         match self.current() {
             Some(SyntaxKind::Functor) => self.bump(),
             Some(token) => {
+                self.missing();
                 self.recover(
+                    ParserErrorKind::ExpectedLiteral,
                     format!("expected literal, got {:?}", token),
                     |_| false,
                     |t| t == SyntaxKind::Dot || t == SyntaxKind::Semi,
@@ -232,63 +628,89 @@ impl Parser<'_> {
                 self.builder.finish_node();
                 return;
             }
-            None => self.unexpected_eof = true,
+            None => {
+                self.missing();
+                self.unexpected_eof = true;
+            }
         }
 
         if self.current() == Some(SyntaxKind::OpenParen) {
-            self.builder.start_node(SyntaxKind::LiteralTerms.into());
-            self.bump();
+            self.parse_literal_terms();
+        }
 
-            self.parse_term();
-            while let Some(SyntaxKind::Comma) = self.current() {
-                self.bump();
-                self.parse_term();
+        if self.current() == Some(SyntaxKind::OpenBracket) {
+            self.parse_literal_annotations();
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn parse_literal_terms(&mut self) {
+        self.builder.start_node(SyntaxKind::LiteralTerms.into());
+        self.bump();
+
+        self.parse_terms(SyntaxKind::CloseParen, ')');
+
+        match self.current() {
+            Some(SyntaxKind::CloseParen) => self.bump(),
+            Some(token) => {
+                self.recover(
+                    ParserErrorKind::UnclosedParen,
+                    format!("expected ')' to close literal, got {:?}", token),
+                    |t| t == SyntaxKind::CloseParen,
+                    |t| t == SyntaxKind::Dot || t == SyntaxKind::Semi,
+                );
             }
+            None => self.unexpected_eof = true,
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn parse_literal_annotations(&mut self) {
+        self.builder
+            .start_node(SyntaxKind::LiteralAnnotations.into());
+        self.bump();
+
+        if self.current() != Some(SyntaxKind::CloseBracket) {
+            self.parse_terms(SyntaxKind::CloseBracket, ']');
 
             match self.current() {
-                Some(SyntaxKind::CloseParen) => self.bump(),
+                Some(SyntaxKind::CloseBracket) => self.bump(),
                 Some(token) => {
                     self.recover(
-                        format!("expected ')' to close literal, got {:?}", token),
-                        |t| t == SyntaxKind::CloseParen,
+                        ParserErrorKind::UnclosedBracket,
+                        format!("expected ']' to close literal annotation, got {:?}", token),
+                        |t| t == SyntaxKind::CloseBracket,
                         |t| t == SyntaxKind::Dot || t == SyntaxKind::Semi,
                     );
                 }
                 None => self.unexpected_eof = true,
             }
-
-            self.builder.finish_node();
-        }
-
-        if self.current() == Some(SyntaxKind::OpenBracket) {
-            self.builder
-                .start_node(SyntaxKind::LiteralAnnotations.into());
+        } else {
             self.bump();
+        }
 
-            if self.current() != Some(SyntaxKind::CloseBracket) {
-                self.parse_term();
-                while let Some(SyntaxKind::Comma) = self.current() {
-                    self.bump();
-                    self.parse_term();
-                }
+        self.builder.finish_node();
+    }
 
-                match self.current() {
-                    Some(SyntaxKind::CloseBracket) => self.bump(),
-                    Some(token) => {
-                        self.recover(
-                            format!("expected ']' to close literal annotation, got {:?}", token),
-                            |t| t == SyntaxKind::CloseBracket,
-                            |t| t == SyntaxKind::Dot || t == SyntaxKind::Semi,
-                        );
-                    }
-                    None => self.unexpected_eof = true,
-                }
+    /// Parses comma-separated terms, diagnosing a trailing comma before the
+    /// closing token.
+    fn parse_terms(&mut self, close: SyntaxKind, close_char: char) {
+        self.parse_term();
+        while let Some(SyntaxKind::Comma) = self.current() {
+            let comma = self.tokens.current_token_idx();
+            self.bump();
+            if self.current() == Some(close) {
+                self.push_error_at(
+                    ParserErrorKind::TrailingComma,
+                    format!("trailing comma before '{}'", close_char),
+                    comma,
+                );
+                break;
             }
-
-            self.builder.finish_node();
+            self.parse_term();
         }
-
-        self.builder.finish_node();
     }
 
     fn parse_term(&mut self) {
@@ -328,8 +750,8 @@ impl Parser<'_> {
 
     fn parse_comparison(&mut self) {
         let checkpoint = self.builder.checkpoint();
-        self.parse_additive_expression();
-        if self
+        self.parse_shift_expression();
+        while self
             .current()
             .and_then(|t| t.comparison_operator())
             .is_some()
@@ -337,6 +759,24 @@ impl Parser<'_> {
             self.builder
                 .start_node_at(checkpoint, SyntaxKind::Comparison.into());
             self.bump();
+            self.parse_shift_expression();
+            self.builder.finish_node();
+        }
+    }
+
+    /// Bit-shift operators are not part of the language, but they would
+    /// otherwise be confusingly read as two comparisons.
+    fn parse_shift_expression(&mut self) {
+        let checkpoint = self.builder.checkpoint();
+        self.parse_additive_expression();
+        while let Some(SyntaxKind::ShiftLeft | SyntaxKind::ShiftRight) = self.current() {
+            self.push_error(
+                ParserErrorKind::UnsupportedOperator,
+                "bit-shift operators are not supported",
+            );
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::ShiftExpression.into());
+            self.bump();
             self.parse_additive_expression();
             self.builder.finish_node();
         }
@@ -404,7 +844,7 @@ impl Parser<'_> {
                 | SyntaxKind::False
                 | SyntaxKind::String,
             ) => self.bump(),
-            Some(SyntaxKind::Functor) => self.parse_literal(),
+            Some(SyntaxKind::Functor | SyntaxKind::Tilde) => self.parse_literal(),
             Some(SyntaxKind::OpenBracket) => self.parse_list(),
             Some(SyntaxKind::OpenParen) => {
                 self.bump();
@@ -412,6 +852,7 @@ impl Parser<'_> {
                 match self.current() {
                     Some(SyntaxKind::CloseParen) => self.bump(),
                     Some(token) => self.recover(
+                        ParserErrorKind::UnclosedParen,
                         format!("expected ')', got {:?}", token),
                         |t| t == SyntaxKind::CloseParen,
                         |t| t == SyntaxKind::Semi || t == SyntaxKind::Dot,
@@ -420,7 +861,9 @@ impl Parser<'_> {
                 }
             }
             Some(token) => {
+                self.missing();
                 self.recover(
+                    ParserErrorKind::ExpectedTerm,
                     format!("expected atom, got {:?}", token),
                     |_| false,
                     |t| {
@@ -428,7 +871,10 @@ impl Parser<'_> {
                     },
                 );
             }
-            None => self.unexpected_eof = true,
+            None => {
+                self.missing();
+                self.unexpected_eof = true;
+            }
         }
     }
 
@@ -437,25 +883,66 @@ impl Parser<'_> {
         match self.current() {
             Some(SyntaxKind::OpenBracket) => self.bump(),
             Some(token) => {
-                self.recover(format!("expected '[' for list, got {:?}", token), |_| false, |t| t == SyntaxKind::Semi || t == SyntaxKind::Dot);
+                self.recover(
+                    ParserErrorKind::ExpectedTerm,
+                    format!("expected '[' for list, got {:?}", token),
+                    |_| false,
+                    |t| t == SyntaxKind::Semi || t == SyntaxKind::Dot,
+                );
                 self.builder.finish_node();
                 return;
             }
             None => self.unexpected_eof = true,
         }
+        let mut trailing_comma = None;
+        let mut empty = true;
         loop {
-            if self.current() == Some(SyntaxKind::CloseBracket) {
-                self.bump();
-                break;
+            match self.current() {
+                Some(SyntaxKind::CloseBracket) => {
+                    if let Some(comma) = trailing_comma {
+                        self.push_error_at(
+                            ParserErrorKind::TrailingComma,
+                            "trailing comma before ']'",
+                            comma,
+                        );
+                    }
+                    self.bump();
+                    break;
+                }
+                Some(SyntaxKind::Or) if empty => {
+                    self.push_error(
+                        ParserErrorKind::InvalidListTail,
+                        "list tail must follow at least one element",
+                    );
+                    self.parse_list_tail();
+                    break;
+                }
+                _ => (),
             }
 
             self.parse_conjunction();
+            empty = false;
 
+            trailing_comma = None;
             match self.current() {
-                Some(SyntaxKind::Comma | SyntaxKind::Or) => self.bump(),
+                Some(SyntaxKind::Comma) => {
+                    trailing_comma = Some(self.tokens.current_token_idx());
+                    self.bump();
+                }
+                Some(SyntaxKind::Or) => {
+                    // Elements are parsed without disjunctions, so a `|` at
+                    // this level always starts the tail.
+                    self.parse_list_tail();
+                    break;
+                }
                 Some(SyntaxKind::CloseBracket) => continue,
                 Some(token) => {
-                    self.recover(format!("expected ',' or '|' or ']', got {:?}", token), |t| t == SyntaxKind::CloseBracket, |t| t == SyntaxKind::Comma || t == SyntaxKind::Dot);
+                    self.recover(
+                        ParserErrorKind::UnclosedBracket,
+                        format!("expected ',' or '|' or ']', got {:?}", token),
+                        |t| t == SyntaxKind::CloseBracket,
+                        |t| t == SyntaxKind::Comma || t == SyntaxKind::Dot,
+                    );
                     break;
                 }
                 None => {
@@ -468,13 +955,34 @@ impl Parser<'_> {
         self.builder.finish_node();
     }
 
+    /// Parses `| T` and the closing `]` of a list.
+    fn parse_list_tail(&mut self) {
+        self.builder.start_node(SyntaxKind::ListTail.into());
+        assert!(self.current() == Some(SyntaxKind::Or));
+        self.bump();
+        self.parse_conjunction();
+        self.builder.finish_node();
+
+        match self.current() {
+            Some(SyntaxKind::CloseBracket) => self.bump(),
+            Some(_) => self.recover(
+                ParserErrorKind::InvalidListTail,
+                "list tail must be the last element",
+                |t| t == SyntaxKind::CloseBracket,
+                |t| t == SyntaxKind::Dot,
+            ),
+            None => self.unexpected_eof = true,
+        }
+    }
+
     fn recover(
         &mut self,
+        kind: ParserErrorKind,
         message: impl Into<String>,
         mut until_inclusive: impl FnMut(SyntaxKind) -> bool,
         mut until_exclusive: impl FnMut(SyntaxKind) -> bool,
     ) {
-        self.push_error(message);
+        self.push_error(kind, message);
         self.builder.start_node(SyntaxKind::Error.into());
         while let Some(token) = self.current() {
             if until_exclusive(token) {
@@ -488,10 +996,333 @@ impl Parser<'_> {
         self.builder.finish_node();
     }
 
-    fn push_error(&mut self, message: impl Into<String>) {
+    /// Marks the current position as where an expected construct is absent.
+    fn missing(&mut self) {
+        self.builder.start_node(SyntaxKind::Missing.into());
+        self.builder.finish_node();
+    }
+
+    fn push_error(&mut self, kind: ParserErrorKind, message: impl Into<String>) {
+        self.push_error_at(kind, message, self.tokens.current_token_idx());
+    }
+
+    fn push_error_at(
+        &mut self,
+        kind: ParserErrorKind,
+        message: impl Into<String>,
+        token_idx: TokenIdx,
+    ) {
         self.errors.push(ParserError {
+            kind,
             message: message.into(),
-            token_idx: self.tokens.current_token_idx(),
+            token_idx,
+            severity: Severity::Error,
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::Comparison,
+        syntax::{sexpr_tree, ComparisonOperator},
+    };
+
+    fn errors(source: &str) -> Vec<(String, &str)> {
+        let lexed = LexedStr::new(source);
+        let parsed = parse(&lexed);
+        assert!(!parsed.unexpected_eof);
+        parsed
+            .errors
+            .into_iter()
+            .map(|error| {
+                let range = lexed.token_range(error.token_idx);
+                (error.message, &source[range])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_affected_item_range() {
+        let source = "at(home).\nnear(X) :- at(X).\n\n";
+        let lexed = LexedStr::new(source);
+        let parsed = parse(&lexed);
+        let affected = |delete: Range<usize>, insert: &str| -> &str {
+            let edit = TextEdit {
+                delete,
+                insert: insert.to_owned(),
+            };
+            &source[affected_item_range(&parsed, &edit)]
+        };
+
+        assert_eq!(affected(3..7, "work"), "at(home).");
+        assert_eq!(affected(9..9, " "), "at(home).");
+        assert_eq!(affected(5..12, ""), "at(home).\nnear(X) :- at(X).");
+        assert_eq!(affected(28..28, "!go."), "\n\n");
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for source in ["", "   ", "// just a comment", "\n/* block */\n"] {
+            let lexed = LexedStr::new(source);
+            assert!(lexed.errors.is_empty());
+
+            let parsed = parse(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            assert!(!parsed.unexpected_eof);
+
+            let root = parsed.syntax();
+            assert_eq!(root.kind(), SyntaxKind::Root);
+            assert_eq!(root.children().count(), 0);
+            assert_eq!(root.to_string(), source);
+
+            let parsed = parse_term(&lexed);
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            assert!(parsed.unexpected_eof);
+        }
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let kinds = |source: &str| -> Vec<ParserErrorKind> {
+            let lexed = LexedStr::new(source);
+            parse(&lexed)
+                .errors
+                .into_iter()
+                .map(|error| error.kind)
+                .collect()
+        };
+
+        assert_eq!(kinds("at(home)\n+!go <- x."), [ParserErrorKind::MissingDot]);
+        assert_eq!(kinds("p :- q r."), [ParserErrorKind::MissingDot]);
+        assert_eq!(kinds("! 1."), [ParserErrorKind::ExpectedLiteral]);
+        assert_eq!(kinds("foo(a, b."), [ParserErrorKind::UnclosedParen]);
+        assert_eq!(kinds("foo[a b]."), [ParserErrorKind::UnclosedBracket]);
+        assert_eq!(kinds(") foo."), [ParserErrorKind::UnexpectedToken]);
+        assert_eq!(kinds("foo(a,)."), [ParserErrorKind::TrailingComma]);
+        assert_eq!(
+            kinds("{ include(\"a.asl\") ."),
+            [ParserErrorKind::UnclosedBrace]
+        );
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let sexpr = |source: &str| sexpr_tree(&parse(&LexedStr::new(source)).syntax());
+        assert_eq!(
+            sexpr("+!count : c(N) <- while (N < 3) { -+c(N + 1); .print(N) }; done."),
+            r#"(Root (Plan + ! (Literal count) : (PlanContext (Literal c (LiteralTerms "(" N ")"))) <- (Body (WhileLoop while "(" (Comparison N < 3) ")" (Body { (Formula -+ (Literal c (LiteralTerms "(" (AdditiveExpression N + 1) ")"))) ; (Formula (Literal .print (LiteralTerms "(" N ")"))) })) ; (Formula (Literal done)) .)))"#
+        );
+        assert_eq!(errors("+!g <- while (true) {}."), []);
+
+        assert_eq!(
+            errors("+!g <- while (true) { a b }; c."),
+            [("expected ';' or '}', got Functor".to_owned(), "b")]
+        );
+        assert_eq!(
+            sexpr("+!g <- while (true) { a b }; c."),
+            r#"(Root (Plan + ! (Literal g) <- (Body (WhileLoop while "(" true ")" (Body { (Formula (Literal a)) (Error b) })) ; (Formula (Literal c)) .)))"#
+        );
+        assert_eq!(
+            errors("+!g <- while (true) { a; b. +!h <- c."),
+            [("expected ';' or '}', got Dot".to_owned(), ".")]
+        );
+        assert_eq!(
+            errors("+!g <- while true { a }."),
+            [("expected '(', got True".to_owned(), "true")]
+        );
+        assert_eq!(
+            errors("+!g <- while (true) a."),
+            [("expected '{', got Functor".to_owned(), "a")]
+        );
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let sexpr = |source: &str| sexpr_tree(&parse(&LexedStr::new(source)).syntax());
+        assert_eq!(
+            errors("+!g : l(L) <- for (.member(X, L)) { .print(X) }; done."),
+            []
+        );
+        assert_eq!(
+            sexpr("+!g : l(L) <- for (.member(X, L)) { .print(X) }; done."),
+            r#"(Root (Plan + ! (Literal g) : (PlanContext (Literal l (LiteralTerms "(" L ")"))) <- (Body (ForLoop for "(" (Literal .member (LiteralTerms "(" X , L ")")) ")" (Body { (Formula (Literal .print (LiteralTerms "(" X ")"))) })) ; (Formula (Literal done)) .)))"#
+        );
+
+        assert_eq!(
+            errors("+!g <- for (.member(X, L) { a }; b."),
+            [("expected ')', got OpenBrace".to_owned(), "{")]
+        );
+        assert_eq!(
+            errors("+!g <- for (.member(X, L))) { a }; b."),
+            [("expected '{', got CloseParen".to_owned(), ")")]
+        );
+        assert_eq!(
+            errors("+!g <- for (.member(X, L)) a; b."),
+            [("expected '{', got Functor".to_owned(), "a")]
+        );
+        assert_eq!(
+            sexpr("+!g <- for (.member(X, L)) a; b."),
+            r#"(Root (Plan + ! (Literal g) <- (Body (ForLoop for "(" (Literal .member (LiteralTerms "(" X , L ")")) ")" (Body (Formula (Literal a)))) ; (Formula (Literal b)) .)))"#
+        );
+    }
+
+    #[test]
+    fn test_if_then_else() {
+        let sexpr = |source: &str| {
+            let parsed = parse(&LexedStr::new(source));
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            sexpr_tree(&parsed.syntax())
+        };
+        assert_eq!(
+            sexpr("+!g <- if (X > 1) { a }."),
+            r#"(Root (Plan + ! (Literal g) <- (Body (IfThenElse if "(" (Comparison X > 1) ")" (Body { (Formula (Literal a)) })) .)))"#
+        );
+        assert_eq!(
+            sexpr("+!g <- if (X > 1) { a } else { b; c }; d."),
+            r#"(Root (Plan + ! (Literal g) <- (Body (IfThenElse if "(" (Comparison X > 1) ")" (Body { (Formula (Literal a)) }) else (Body { (Formula (Literal b)) ; (Formula (Literal c)) })) ; (Formula (Literal d)) .)))"#
+        );
+        assert_eq!(
+            sexpr("+!g <- if (a) { x } else if (b) { y } elif (c) { z } else {}."),
+            r#"(Root (Plan + ! (Literal g) <- (Body (IfThenElse if "(" (Literal a) ")" (Body { (Formula (Literal x)) }) else (IfThenElse if "(" (Literal b) ")" (Body { (Formula (Literal y)) }) (IfThenElse elif "(" (Literal c) ")" (Body { (Formula (Literal z)) }) else (Body { })))) .)))"#
+        );
+
+        assert_eq!(
+            errors("+!g <- if (X < ) { a } else { b }."),
+            [("expected atom, got CloseParen".to_owned(), ")")]
+        );
+        assert_eq!(
+            errors("+!g <- if (a) b else { c }."),
+            [("expected '{', got Functor".to_owned(), "b")]
+        );
+    }
+
+    #[test]
+    fn test_list_shapes() {
+        let sexpr = |source: &str| {
+            let parsed = parse_term(&LexedStr::new(source));
+            assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+            sexpr_tree(&parsed.syntax())
+        };
+
+        assert_eq!(sexpr("[]"), "(Root (List [ ]))");
+        assert_eq!(
+            sexpr("[a, b, c]"),
+            "(Root (List [ (Literal a) , (Literal b) , (Literal c) ]))"
+        );
+        assert_eq!(
+            sexpr("[1 + 2 * X, [Y], []]"),
+            "(Root (List [ (AdditiveExpression 1 + (MultiplicativeExpression 2 * X)) , \
+             (List [ Y ]) , (List [ ]) ]))"
+        );
+    }
+
+    #[test]
+    fn test_list_tail() {
+        assert_eq!(errors("foo([a | T])."), []);
+        assert_eq!(errors("foo([a, b])."), []);
+        assert_eq!(errors("foo([(a | b), c | [d]])."), []);
+        assert_eq!(
+            errors("foo([a | b, c])."),
+            [("list tail must be the last element".to_owned(), ",")]
+        );
+        assert_eq!(
+            errors("foo([a | b | c]). bar."),
+            [("list tail must be the last element".to_owned(), "|")]
+        );
+        assert_eq!(
+            errors("foo([| T])."),
+            [("list tail must follow at least one element".to_owned(), "|")]
+        );
+
+        let sexpr = |source: &str| sexpr_tree(&parse_term(&LexedStr::new(source)).syntax());
+        assert_eq!(sexpr("[H|T]"), "(Root (List [ H (ListTail | T) ]))");
+        assert_eq!(
+            sexpr("[a,b|T]"),
+            "(Root (List [ (Literal a) , (Literal b) (ListTail | T) ]))"
+        );
+        assert_eq!(
+            sexpr("[a|b,c]"),
+            "(Root (List [ (Literal a) (ListTail | (Literal b)) (Error , c ])))"
+        );
+    }
+
+    #[test]
+    fn test_trailing_comma() {
+        assert_eq!(
+            errors("foo(a, b,)."),
+            [("trailing comma before ')'".to_owned(), ",")]
+        );
+        assert_eq!(
+            errors("foo([a, b,])."),
+            [("trailing comma before ']'".to_owned(), ",")]
+        );
+        assert_eq!(
+            errors("foo[a, b,]."),
+            [("trailing comma before ']'".to_owned(), ",")]
+        );
+        assert_eq!(errors("foo(a, [b, c])[d, e]."), []);
+    }
+
+    #[test]
+    fn test_arith_equality() {
+        let lexed = LexedStr::new("+!go : X =:= 1.0 & Y =\\= 2 <- act.");
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let ops: Vec<_> = parsed
+            .syntax()
+            .descendants()
+            .filter_map(Comparison::cast)
+            .filter_map(|comparison| comparison.op())
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                ComparisonOperator::ArithEqual,
+                ComparisonOperator::ArithNotEqual
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shift_operators() {
+        assert_eq!(
+            errors("+!go : X >> 2 <- act."),
+            [("bit-shift operators are not supported".to_owned(), ">>")]
+        );
+        assert_eq!(
+            errors("+!go : X > 2 & Y << 1 < 3 <- act."),
+            [("bit-shift operators are not supported".to_owned(), "<<")]
+        );
+    }
+
+    #[test]
+    fn test_parse_tokens() {
+        let parsed = parse_tokens(vec![(SyntaxKind::Functor, "foo"), (SyntaxKind::Dot, ".")]);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(
+            sexpr_tree(&parsed.syntax()),
+            "(Root (Belief (Literal foo) .))"
+        );
+
+        let tokens = vec![
+            (SyntaxKind::Plus, "+"),
+            (SyntaxKind::Functor, "b"),
+            (SyntaxKind::Whitespace, " "),
+            (SyntaxKind::Arrow, "<-"),
+            (SyntaxKind::Whitespace, " "),
+            (SyntaxKind::Functor, "act"),
+            (SyntaxKind::Dot, "."),
+        ];
+        let source = "+b <- act.";
+        assert_eq!(
+            format!("{:#?}", parse_tokens(tokens).syntax()),
+            format!("{:#?}", parse(&LexedStr::new(source)).syntax())
+        );
+
+        let parsed = parse_tokens(vec![(SyntaxKind::Functor, "foo")]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].kind, ParserErrorKind::MissingDot);
+    }
+}
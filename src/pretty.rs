@@ -0,0 +1,415 @@
+//! Printing of terms in source syntax. Terms are converted to a document of
+//! text and optional line breaks, which is laid out Wadler/Leijen-style:
+//! a group is printed on one line if it fits into the remaining width, and
+//! otherwise its line breaks become newlines.
+
+use std::fmt;
+
+use crate::runtime::{BinaryOperator, UnaryOparator, Value};
+
+const INDENT: usize = 4;
+
+#[derive(Debug)]
+enum Doc {
+    Text(String),
+    /// A line break, or `flat` when the enclosing group fits on one line.
+    Line {
+        flat: &'static str,
+    },
+    Nest(Box<Doc>),
+    Concat(Vec<Doc>),
+    Group(Box<Doc>),
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn softline() -> Doc {
+    Doc::Line { flat: "" }
+}
+
+fn line() -> Doc {
+    Doc::Line { flat: " " }
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+/// `open`, the comma-separated `items`, then `close`. When broken, every item
+/// goes on its own indented line.
+fn delimited(open: &str, items: Vec<Doc>, tail: Option<Doc>, close: &str) -> Doc {
+    let mut inner = vec![softline()];
+    let len = items.len();
+    for (i, item) in items.into_iter().enumerate() {
+        inner.push(item);
+        if i + 1 < len {
+            inner.push(text(","));
+            inner.push(line());
+        }
+    }
+    if let Some(tail) = tail {
+        inner.push(line());
+        inner.push(text("| "));
+        inner.push(tail);
+    }
+    group(Doc::Concat(vec![
+        text(open),
+        nest(Doc::Concat(inner)),
+        softline(),
+        text(close),
+    ]))
+}
+
+pub(crate) fn unary_symbol(op: UnaryOparator) -> &'static str {
+    match op {
+        UnaryOparator::Pos => "+",
+        UnaryOparator::Neg => "-",
+        UnaryOparator::Not => "not ",
+    }
+}
+
+pub(crate) fn binary_symbol(op: BinaryOperator) -> &'static str {
+    match op {
+        BinaryOperator::Or => "|",
+        BinaryOperator::And => "&",
+        BinaryOperator::LtEq => "<=",
+        BinaryOperator::GtEq => ">=",
+        BinaryOperator::NotEqual => "\\==",
+        BinaryOperator::Equal => "==",
+        BinaryOperator::Decompose => "=..",
+        BinaryOperator::ArithEqual => "=:=",
+        BinaryOperator::ArithNotEqual => "=\\=",
+        BinaryOperator::Eq => "=",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Plus => "+",
+        BinaryOperator::Minus => "-",
+        BinaryOperator::Mul => "*",
+        BinaryOperator::Div => "/",
+        BinaryOperator::FloorDiv => "div",
+        BinaryOperator::Mod => "mod",
+        BinaryOperator::Pow => "**",
+    }
+}
+
+fn binary_precedence(op: BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::LtEq
+        | BinaryOperator::GtEq
+        | BinaryOperator::NotEqual
+        | BinaryOperator::Equal
+        | BinaryOperator::Decompose
+        | BinaryOperator::ArithEqual
+        | BinaryOperator::ArithNotEqual
+        | BinaryOperator::Eq
+        | BinaryOperator::Lt
+        | BinaryOperator::Gt => 4,
+        BinaryOperator::Plus | BinaryOperator::Minus => 5,
+        BinaryOperator::Mul
+        | BinaryOperator::Div
+        | BinaryOperator::FloorDiv
+        | BinaryOperator::Mod => 6,
+        BinaryOperator::Pow => 8,
+    }
+}
+
+/// Binding strength of a term, following the grammar from `|` (loosest) to
+/// atoms (tightest).
+fn precedence(value: &Value) -> u8 {
+    match value {
+        Value::UnaryOp {
+            op: UnaryOparator::Not,
+            ..
+        } => 3,
+        Value::UnaryOp { .. } => 7,
+        Value::Integer(n) if *n < 0 => 7,
+        Value::Float(n) if n.is_sign_negative() => 7,
+        Value::BinaryOp { op, .. } => binary_precedence(*op),
+        _ => 9,
+    }
+}
+
+fn operand(value: &Value, min_precedence: u8) -> Doc {
+    if precedence(value) < min_precedence {
+        Doc::Concat(vec![text("("), to_doc(value), text(")")])
+    } else {
+        to_doc(value)
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn to_doc(value: &Value) -> Doc {
+    match value {
+        Value::Integer(n) => text(n.to_string()),
+        Value::Float(n) => text(format!("{:?}", n)),
+        Value::String(s) => text(escape(s)),
+        Value::Variable(id) => text(format!("_V{}", id.0)),
+        Value::Term {
+            strong_negation,
+            functor,
+            args,
+            annotations,
+        } => {
+            let mut parts = vec![text(if *strong_negation {
+                format!("~{}", functor)
+            } else {
+                functor.clone()
+            })];
+            if !args.is_empty() {
+                parts.push(delimited("(", args.iter().map(to_doc).collect(), None, ")"));
+            }
+            if !annotations.is_empty() {
+                parts.push(delimited(
+                    "[",
+                    annotations.iter().map(to_doc).collect(),
+                    None,
+                    "]",
+                ));
+            }
+            Doc::Concat(parts)
+        }
+        // `|` separates the tail, so disjunctions need parentheses.
+        Value::List(list) => delimited(
+            "[",
+            list.iter().map(|element| operand(element, 2)).collect(),
+            list.last_tail().map(|tail| operand(tail, 2)),
+            "]",
+        ),
+        Value::UnaryOp { op, value } => {
+            let min = match op {
+                UnaryOparator::Not => 3,
+                UnaryOparator::Pos | UnaryOparator::Neg => 8,
+            };
+            Doc::Concat(vec![text(unary_symbol(*op)), operand(value, min)])
+        }
+        Value::BinaryOp { op, left, right } => {
+            let p = binary_precedence(*op);
+            let (left_min, right_min) = match op {
+                BinaryOperator::Pow => (9, 7),
+                _ if p == 4 => (p + 1, p + 1),
+                _ => (p, p + 1),
+            };
+            group(Doc::Concat(vec![
+                operand(left, left_min),
+                text(" "),
+                text(binary_symbol(*op)),
+                nest(Doc::Concat(vec![line(), operand(right, right_min)])),
+            ]))
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Whether the rest of the line fits into `width`. Looks at `next`, then at
+/// the pending commands on `stack` until the next line break.
+fn fits(mut width: isize, next: (usize, Mode, &Doc), stack: &[(usize, Mode, &Doc)]) -> bool {
+    let mut local = vec![next];
+    let mut rest = stack.iter().rev();
+    while width >= 0 {
+        let (indent, mode, doc) = match local.pop() {
+            Some(command) => command,
+            None => match rest.next() {
+                Some(command) => *command,
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => width -= s.chars().count() as isize,
+            Doc::Line { flat } => match mode {
+                Mode::Flat => width -= flat.len() as isize,
+                Mode::Break => return true,
+            },
+            Doc::Nest(inner) => local.push((indent + INDENT, mode, inner)),
+            Doc::Concat(parts) => local.extend(parts.iter().rev().map(|part| (indent, mode, part))),
+            Doc::Group(inner) => local.push((indent, mode, inner)),
+        }
+    }
+    false
+}
+
+fn layout(doc: &Doc, width: usize) -> String {
+    let width = width.min(isize::MAX as usize) as isize;
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column += s.chars().count() as isize;
+            }
+            Doc::Line { flat } => match mode {
+                Mode::Flat => {
+                    out.push_str(flat);
+                    column += flat.len() as isize;
+                }
+                Mode::Break => {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    column = indent as isize;
+                }
+            },
+            Doc::Nest(inner) => stack.push((indent + INDENT, mode, inner)),
+            Doc::Concat(parts) => stack.extend(parts.iter().rev().map(|part| (indent, mode, part))),
+            Doc::Group(inner) => {
+                let mode = if mode == Mode::Flat
+                    || fits(width - column, (indent, Mode::Flat, inner), &stack)
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.push((indent, mode, inner));
+            }
+        }
+    }
+
+    out
+}
+
+fn write_flat(doc: &Doc, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match doc {
+        Doc::Text(s) => f.write_str(s),
+        Doc::Line { flat } => f.write_str(flat),
+        Doc::Nest(inner) | Doc::Group(inner) => write_flat(inner, f),
+        Doc::Concat(parts) => parts.iter().try_for_each(|part| write_flat(part, f)),
+    }
+}
+
+/// Formats a term in source syntax, breaking argument lists, annotations,
+/// lists and operators over multiple indented lines where they would not
+/// fit into `width` columns.
+pub fn pretty_print(value: &Value, width: usize) -> String {
+    layout(&to_doc(value), width)
+}
+
+impl fmt::Display for Value {
+    /// Formats a term in source syntax on a single line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_flat(&to_doc(self), f)
+    }
+}
+
+impl fmt::Debug for Value {
+    /// Same as [`Display`](fmt::Display), so that failing assertions show
+    /// `f(1, [a])` instead of nested enum variants.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        for source in [
+            "foo",
+            "foo(1, -2, 3.5, \"a\\\"b\\n\")[source(self)]",
+            "[]",
+            "[a, [b], c]",
+            "(1 + 2) * 3 - 4 / 5 mod 6",
+            "1 - (2 - 3)",
+            "2 ** 3 ** 4",
+            "(2 ** 3) ** 4",
+            "-(1 + 2)",
+            "not (a & b) | c",
+            "(a | b) & not c",
+            "[(a | b), c | (d | e)]",
+        ] {
+            assert_eq!(Value::parse(source).unwrap().to_string(), source);
+        }
+
+        assert_eq!(Value::parse("[H|T]").unwrap().to_string(), "[_V0 | _V1]");
+        assert_eq!(Value::parse("true").unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn test_debug() {
+        let value = Value::parse("f(1, [a])[source(self)]").unwrap();
+        assert_eq!(format!("{:?}", value), "f(1, [a])[source(self)]");
+        assert_eq!(format!("{:?}", Some(Value::Integer(-3))), "Some(-3)");
+    }
+
+    /// Fails on purpose to show how a mismatch is reported, with both sides
+    /// in source syntax:
+    ///
+    /// ```text
+    /// assertion `left == right` failed
+    ///   left: f(1, [a])
+    ///  right: f(1, [b])
+    /// ```
+    #[test]
+    #[ignore = "fails on purpose"]
+    fn test_debug_assertion_output() {
+        assert_eq!(
+            Value::parse("f(1, [a])").unwrap(),
+            Value::parse("f(1, [b])").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let value = Value::parse(
+            "position(robot(r2d2), coordinates(12, 34, 56), [north, east, south])[source(percept)]",
+        )
+        .unwrap();
+
+        assert_eq!(pretty_print(&value, 200), value.to_string());
+        assert_eq!(
+            pretty_print(&value, 40),
+            "position(\n\
+             \x20   robot(r2d2),\n\
+             \x20   coordinates(12, 34, 56),\n\
+             \x20   [north, east, south]\n\
+             )[source(percept)]"
+        );
+        assert_eq!(
+            pretty_print(&value, 20),
+            "position(\n\
+             \x20   robot(r2d2),\n\
+             \x20   coordinates(\n\
+             \x20       12,\n\
+             \x20       34,\n\
+             \x20       56\n\
+             \x20   ),\n\
+             \x20   [\n\
+             \x20       north,\n\
+             \x20       east,\n\
+             \x20       south\n\
+             \x20   ]\n\
+             )[source(percept)]"
+        );
+    }
+}
@@ -1,35 +1,1480 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
 
-struct VariableId(u64);
+use crate::{
+    ast::Term,
+    error::PheresError,
+    lower::lower_term,
+    parser::parse_term,
+    syntax::{FormulaType, LexedStr, TriggerKind},
+};
 
-enum Value {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VariableId(pub u64);
+
+/// Terms are formatted for debugging in source syntax too, see the
+/// implementations in [`crate::pretty`].
+#[derive(Clone, PartialEq)]
+pub enum Value {
     Integer(i64),
     Float(f64),
     String(String),
-    Term { functor: String, args: Vec<Value>, annotations: Vec<Value> },
+    Term {
+        /// Jason-style strong negation `~`. Negation as failure is
+        /// [`UnaryOparator::Not`] instead.
+        strong_negation: bool,
+        functor: String,
+        args: Vec<Value>,
+        annotations: Vec<Value>,
+    },
     List(Box<List>),
     Variable(VariableId),
-    UnaryOp { op: UnaryOparator, value: Box<Value> },
-    BinaryOp { op: BinaryOperator, left: Box<Value>, right: Box<Value> },
+    UnaryOp {
+        op: UnaryOparator,
+        value: Box<Value>,
+    },
+    BinaryOp {
+        op: BinaryOperator,
+        left: Box<Value>,
+        right: Box<Value>,
+    },
+}
+
+impl Value {
+    /// Lexes, parses and lowers a single term. Each call uses a fresh
+    /// [`Interner`], so variable ids are only consistent within one term.
+    pub fn parse(source: &str) -> Result<Value, PheresError> {
+        let lexed = LexedStr::new(source);
+        if !lexed.errors.is_empty() {
+            return Err(PheresError::Syntax(lexed.errors));
+        }
+
+        let parsed = parse_term(&lexed);
+        if !parsed.errors.is_empty() {
+            return Err(PheresError::Parser(parsed.errors));
+        }
+        if parsed.unexpected_eof {
+            return Err(PheresError::UnexpectedEof);
+        }
+
+        let term = parsed
+            .syntax()
+            .children_with_tokens()
+            .find_map(Term::cast)
+            .ok_or(PheresError::UnexpectedEof)?;
+
+        Ok(lower_term(&term, &mut Interner::default())?)
+    }
+
+    /// Like [`Value::parse`], but also rejects terms with variables, as
+    /// required for beliefs and other data.
+    pub fn parse_ground(source: &str) -> Result<Value, PheresError> {
+        let value = Value::parse(source)?;
+        if value.is_ground() {
+            Ok(value)
+        } else {
+            Err(PheresError::NotGround)
+        }
+    }
+
+    pub fn atom(functor: impl Into<String>) -> Value {
+        Value::Term {
+            strong_negation: false,
+            functor: functor.into(),
+            args: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Annotations of a literal. Other values have none.
+    pub fn annotations(&self) -> &[Value] {
+        match self {
+            Value::Term { annotations, .. } => annotations,
+            _ => &[],
+        }
+    }
+
+    /// Adds an annotation to a literal, unless an equal one is already
+    /// present. Other values are returned unchanged.
+    pub fn with_annotation(mut self, annotation: Value) -> Value {
+        if let Value::Term { annotations, .. } = &mut self {
+            if !annotations.contains(&annotation) {
+                annotations.push(annotation);
+            }
+        }
+        self
+    }
+
+    /// Removes all annotations equal to `pattern` from a literal. Other
+    /// values are returned unchanged.
+    pub fn without_annotation(mut self, pattern: &Value) -> Value {
+        if let Value::Term { annotations, .. } = &mut self {
+            annotations.retain(|annotation| annotation != pattern);
+        }
+        self
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// The numeric value, converting integers to floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Integer(n) => Some(n as f64),
+            Value::Float(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Maximum nesting of the term, where scalars and atoms have depth 1.
+    pub fn depth(&self) -> usize {
+        match self {
+            Value::Term {
+                args, annotations, ..
+            } => {
+                1 + args
+                    .iter()
+                    .chain(annotations)
+                    .map(Value::depth)
+                    .max()
+                    .unwrap_or(0)
+            }
+            Value::List(list) => {
+                1 + list
+                    .iter()
+                    .chain(list.last_tail())
+                    .map(Value::depth)
+                    .max()
+                    .unwrap_or(0)
+            }
+            Value::UnaryOp { value, .. } => 1 + value.depth(),
+            Value::BinaryOp { left, right, .. } => 1 + left.depth().max(right.depth()),
+            Value::Integer(_) | Value::Float(_) | Value::String(_) | Value::Variable(_) => 1,
+        }
+    }
+
+    /// Rebuilds the value bottom-up, applying `f` to every node after its
+    /// children have been rewritten.
+    pub fn map_terms(&self, f: &mut impl FnMut(Value) -> Value) -> Value {
+        let value = match self {
+            Value::Term {
+                strong_negation,
+                functor,
+                args,
+                annotations,
+            } => Value::Term {
+                strong_negation: *strong_negation,
+                functor: functor.clone(),
+                args: args.iter().map(|arg| arg.map_terms(f)).collect(),
+                annotations: annotations
+                    .iter()
+                    .map(|annotation| annotation.map_terms(f))
+                    .collect(),
+            },
+            Value::List(list) => {
+                let mut elements = Vec::new();
+                let mut list = &**list;
+                let tail = loop {
+                    match list {
+                        List::Empty => break List::Empty,
+                        List::Tail(tail) => break List::Tail(tail.map_terms(f)),
+                        List::Element { head, tail } => {
+                            elements.push(head.map_terms(f));
+                            list = tail;
+                        }
+                    }
+                };
+                Value::List(Box::new(List::new(elements, tail)))
+            }
+            Value::UnaryOp { op, value } => Value::UnaryOp {
+                op: *op,
+                value: Box::new(value.map_terms(f)),
+            },
+            Value::BinaryOp { op, left, right } => Value::BinaryOp {
+                op: *op,
+                left: Box::new(left.map_terms(f)),
+                right: Box::new(right.map_terms(f)),
+            },
+            Value::Integer(_) | Value::Float(_) | Value::String(_) | Value::Variable(_) => {
+                self.clone()
+            }
+        };
+        f(value)
+    }
+
+    /// Standard order of terms: variables, numbers, strings, compound terms
+    /// (by arity, then functor, then arguments), lists, and finally
+    /// unevaluated expressions. Numbers compare by value, with a float
+    /// before an equal integer.
+    pub fn standard_cmp(&self, other: &Value) -> Ordering {
+        fn rank(value: &Value) -> u8 {
+            match value {
+                Value::Variable(_) => 0,
+                Value::Integer(_) | Value::Float(_) => 1,
+                Value::String(_) => 2,
+                Value::Term { .. } => 3,
+                Value::List(_) => 4,
+                Value::UnaryOp { .. } | Value::BinaryOp { .. } => 5,
+            }
+        }
+
+        fn cmp_all<'a>(
+            left: impl IntoIterator<Item = &'a Value>,
+            right: impl IntoIterator<Item = &'a Value>,
+        ) -> Ordering {
+            let mut left = left.into_iter();
+            let mut right = right.into_iter();
+            loop {
+                match (left.next(), right.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some(a), Some(b)) => match a.standard_cmp(b) {
+                        Ordering::Equal => continue,
+                        ordering => return ordering,
+                    },
+                }
+            }
+        }
+
+        match (self, other) {
+            (Value::Variable(a), Value::Variable(b)) => a.0.cmp(&b.0),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => a.total_cmp(&(*b as f64)).then(Ordering::Less),
+            (Value::Integer(a), Value::Float(b)) => {
+                (*a as f64).total_cmp(b).then(Ordering::Greater)
+            }
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (
+                Value::Term {
+                    strong_negation: a_negation,
+                    functor: a_functor,
+                    args: a_args,
+                    annotations: a_annotations,
+                },
+                Value::Term {
+                    strong_negation: b_negation,
+                    functor: b_functor,
+                    args: b_args,
+                    annotations: b_annotations,
+                },
+            ) => a_args
+                .len()
+                .cmp(&b_args.len())
+                .then_with(|| a_functor.cmp(b_functor))
+                .then_with(|| a_negation.cmp(b_negation))
+                .then_with(|| cmp_all(a_args, b_args))
+                .then_with(|| cmp_all(a_annotations, b_annotations)),
+            (Value::List(a), Value::List(b)) => {
+                cmp_all(a.iter(), b.iter()).then_with(|| match (a.last_tail(), b.last_tail()) {
+                    (Some(a), Some(b)) => a.standard_cmp(b),
+                    (a, b) => a.is_some().cmp(&b.is_some()),
+                })
+            }
+            (
+                Value::UnaryOp {
+                    op: a_op,
+                    value: a_value,
+                },
+                Value::UnaryOp {
+                    op: b_op,
+                    value: b_value,
+                },
+            ) => (*a_op as u8)
+                .cmp(&(*b_op as u8))
+                .then_with(|| a_value.standard_cmp(b_value)),
+            (
+                Value::BinaryOp {
+                    op: a_op,
+                    left: a_left,
+                    right: a_right,
+                },
+                Value::BinaryOp {
+                    op: b_op,
+                    left: b_left,
+                    right: b_right,
+                },
+            ) => (*a_op as u8)
+                .cmp(&(*b_op as u8))
+                .then_with(|| a_left.standard_cmp(b_left))
+                .then_with(|| a_right.standard_cmp(b_right)),
+            (Value::UnaryOp { .. }, Value::BinaryOp { .. }) => Ordering::Less,
+            (Value::BinaryOp { .. }, Value::UnaryOp { .. }) => Ordering::Greater,
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    /// Hashes functor, arity and arguments recursively, for bucketing
+    /// candidates before an exact comparison. Structurally equal values hash
+    /// equally.
+    pub fn structural_hash(&self, ignore_annotations: bool) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_structure(ignore_annotations, &mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_structure<H: Hasher>(&self, ignore_annotations: bool, state: &mut H) {
+        match self {
+            Value::Integer(n) => {
+                state.write_u8(0);
+                n.hash(state);
+            }
+            Value::Float(f) => {
+                state.write_u8(1);
+                // 0.0 == -0.0, so they must hash equally.
+                (if *f == 0.0 { 0.0 } else { *f }).to_bits().hash(state);
+            }
+            Value::String(s) => {
+                state.write_u8(2);
+                s.hash(state);
+            }
+            Value::Term {
+                strong_negation,
+                functor,
+                args,
+                annotations,
+            } => {
+                state.write_u8(3);
+                strong_negation.hash(state);
+                functor.hash(state);
+                args.len().hash(state);
+                for arg in args {
+                    arg.hash_structure(ignore_annotations, state);
+                }
+                if !ignore_annotations {
+                    annotations.len().hash(state);
+                    for annotation in annotations {
+                        annotation.hash_structure(ignore_annotations, state);
+                    }
+                }
+            }
+            Value::List(list) => {
+                state.write_u8(4);
+                let mut list = &**list;
+                loop {
+                    match list {
+                        List::Empty => {
+                            state.write_u8(0);
+                            break;
+                        }
+                        List::Tail(tail) => {
+                            state.write_u8(1);
+                            tail.hash_structure(ignore_annotations, state);
+                            break;
+                        }
+                        List::Element { head, tail } => {
+                            state.write_u8(2);
+                            head.hash_structure(ignore_annotations, state);
+                            list = tail;
+                        }
+                    }
+                }
+            }
+            Value::Variable(id) => {
+                state.write_u8(5);
+                id.hash(state);
+            }
+            Value::UnaryOp { op, value } => {
+                state.write_u8(6);
+                op.hash(state);
+                value.hash_structure(ignore_annotations, state);
+            }
+            Value::BinaryOp { op, left, right } => {
+                state.write_u8(7);
+                op.hash(state);
+                left.hash_structure(ignore_annotations, state);
+                right.hash_structure(ignore_annotations, state);
+            }
+        }
+    }
+
+    /// Structural equality that skips annotations at every level, so
+    /// `foo[a] == foo[b]`. Consistent with `structural_hash(true)`.
+    pub fn eq_ignoring_annotations(&self, other: &Value) -> bool {
+        fn all_eq<'a>(
+            left: impl ExactSizeIterator<Item = &'a Value>,
+            mut right: impl ExactSizeIterator<Item = &'a Value>,
+        ) -> bool {
+            left.len() == right.len()
+                && left
+                    .zip(&mut right)
+                    .all(|(a, b)| a.eq_ignoring_annotations(b))
+        }
+
+        match (self, other) {
+            (
+                Value::Term {
+                    strong_negation: a_negation,
+                    functor: a_functor,
+                    args: a_args,
+                    ..
+                },
+                Value::Term {
+                    strong_negation: b_negation,
+                    functor: b_functor,
+                    args: b_args,
+                    ..
+                },
+            ) => {
+                a_negation == b_negation
+                    && a_functor == b_functor
+                    && all_eq(a_args.iter(), b_args.iter())
+            }
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(a, b)| a.eq_ignoring_annotations(b))
+                    && match (a.last_tail(), b.last_tail()) {
+                        (Some(a), Some(b)) => a.eq_ignoring_annotations(b),
+                        (a, b) => a.is_none() && b.is_none(),
+                    }
+            }
+            (
+                Value::UnaryOp {
+                    op: a_op,
+                    value: a_value,
+                },
+                Value::UnaryOp {
+                    op: b_op,
+                    value: b_value,
+                },
+            ) => a_op == b_op && a_value.eq_ignoring_annotations(b_value),
+            (
+                Value::BinaryOp {
+                    op: a_op,
+                    left: a_left,
+                    right: a_right,
+                },
+                Value::BinaryOp {
+                    op: b_op,
+                    left: b_left,
+                    right: b_right,
+                },
+            ) => {
+                a_op == b_op
+                    && a_left.eq_ignoring_annotations(b_left)
+                    && a_right.eq_ignoring_annotations(b_right)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Whether the term contains no variables, including list tails.
+    pub fn is_ground(&self) -> bool {
+        match self {
+            Value::Term {
+                args, annotations, ..
+            } => args.iter().chain(annotations).all(Value::is_ground),
+            Value::List(list) => {
+                list.iter().all(Value::is_ground) && list.last_tail().is_none_or(Value::is_ground)
+            }
+            Value::UnaryOp { value, .. } => value.is_ground(),
+            Value::BinaryOp { left, right, .. } => left.is_ground() && right.is_ground(),
+            Value::Variable(_) => false,
+            Value::Integer(_) | Value::Float(_) | Value::String(_) => true,
+        }
+    }
+
+    /// Whether the variable appears anywhere in the term, including
+    /// annotations and list tails. This is purely structural: there are no
+    /// bindings to follow.
+    pub fn occurs(&self, var: VariableId) -> bool {
+        match self {
+            Value::Term {
+                args, annotations, ..
+            } => args
+                .iter()
+                .chain(annotations)
+                .any(|value| value.occurs(var)),
+            Value::List(list) => list
+                .iter()
+                .chain(list.last_tail())
+                .any(|value| value.occurs(var)),
+            Value::UnaryOp { value, .. } => value.occurs(var),
+            Value::BinaryOp { left, right, .. } => left.occurs(var) || right.occurs(var),
+            Value::Variable(id) => *id == var,
+            Value::Integer(_) | Value::Float(_) | Value::String(_) => false,
+        }
+    }
+
+    /// Total number of nodes in the term, including the open tail of a list.
+    pub fn size(&self) -> usize {
+        match self {
+            Value::Term {
+                args, annotations, ..
+            } => {
+                1 + args
+                    .iter()
+                    .chain(annotations)
+                    .map(Value::size)
+                    .sum::<usize>()
+            }
+            Value::List(list) => {
+                1 + list
+                    .iter()
+                    .chain(list.last_tail())
+                    .map(Value::size)
+                    .sum::<usize>()
+            }
+            Value::UnaryOp { value, .. } => 1 + value.size(),
+            Value::BinaryOp { left, right, .. } => 1 + left.size() + right.size(),
+            Value::Integer(_) | Value::Float(_) | Value::String(_) | Value::Variable(_) => 1,
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = PheresError;
+
+    fn from_str(source: &str) -> Result<Value, PheresError> {
+        Value::parse(source)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Integer(n)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Float(n)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+/// Booleans are the atoms `true` and `false`, as lowered from source.
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::atom(if b { "true" } else { "false" })
+    }
 }
 
-enum List {
+/// A linked list. Cloning, comparing and dropping walk the spine
+/// iteratively, so long lists do not overflow the stack.
+#[derive(Debug)]
+pub enum List {
     Empty,
-    Element { head: Value, tail: Box<List> }
+    Element {
+        head: Value,
+        tail: Box<List>,
+    },
+    /// Open tail of a partial list, like `T` in `[H|T]`.
+    Tail(Value),
+}
+
+impl List {
+    /// Builds a list from its elements, ending in `tail`.
+    pub fn new(elements: Vec<Value>, tail: List) -> List {
+        let mut list = tail;
+        for head in elements.into_iter().rev() {
+            list = List::Element {
+                head,
+                tail: Box::new(list),
+            };
+        }
+        list
+    }
+
+    pub fn iter(&self) -> ListIter<'_> {
+        ListIter { list: self }
+    }
+
+    /// The open tail of a partial list, if any.
+    pub fn last_tail(&self) -> Option<&Value> {
+        let mut list = self;
+        loop {
+            match list {
+                List::Empty => return None,
+                List::Tail(tail) => return Some(tail),
+                List::Element { tail, .. } => list = tail,
+            }
+        }
+    }
+
+    /// Number of elements, not counting an open tail.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.iter().cloned().collect()
+    }
+
+    /// The elements of `self` followed by `other`. An open tail of `self` is
+    /// replaced.
+    pub fn concat(&self, other: &List) -> List {
+        List::new(self.to_vec(), other.clone())
+    }
+
+    fn cloned_tail(&self) -> List {
+        match self.last_tail() {
+            Some(tail) => List::Tail(tail.clone()),
+            None => List::Empty,
+        }
+    }
+
+    /// The elements in reverse order, ending in the same tail.
+    pub fn reverse(&self) -> List {
+        self.iter()
+            .fold(self.cloned_tail(), |list, head| List::Element {
+                head: head.clone(),
+                tail: Box::new(list),
+            })
+    }
+}
+
+impl Clone for List {
+    fn clone(&self) -> List {
+        List::new(self.to_vec(), self.cloned_tail())
+    }
 }
 
-enum UnaryOparator {
+impl PartialEq for List {
+    fn eq(&self, other: &List) -> bool {
+        let mut a = self;
+        let mut b = other;
+        loop {
+            match (a, b) {
+                (List::Empty, List::Empty) => return true,
+                (List::Tail(a), List::Tail(b)) => return a == b,
+                (
+                    List::Element {
+                        head: a_head,
+                        tail: a_tail,
+                    },
+                    List::Element {
+                        head: b_head,
+                        tail: b_tail,
+                    },
+                ) if a_head == b_head => {
+                    a = a_tail;
+                    b = b_tail;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl Drop for List {
+    /// Unlinks the spine iteratively. The derived drop glue would recurse
+    /// once per element and overflow the stack on long lists.
+    fn drop(&mut self) {
+        let mut list = match self {
+            List::Element { tail, .. } => std::mem::replace(&mut **tail, List::Empty),
+            _ => return,
+        };
+        while let List::Element { tail, .. } = &mut list {
+            let next = std::mem::replace(&mut **tail, List::Empty);
+            list = next;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ListIter<'a> {
+    list: &'a List,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<&'a Value> {
+        match self.list {
+            List::Empty | List::Tail(_) => None,
+            List::Element { head, tail } => {
+                self.list = tail;
+                Some(head)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOparator {
+    Pos,
     Neg,
+    Not,
 }
 
-enum BinaryOperator {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    LtEq,
+    GtEq,
+    NotEqual,
+    Equal,
+    Decompose,
+    ArithEqual,
+    ArithNotEqual,
+    Eq,
+    Lt,
+    Gt,
     Plus,
+    Minus,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+}
+
+/// Assigns ids to variable names.
+#[derive(Debug, Default)]
+pub struct Interner {
+    variables: HashMap<String, VariableId>,
+    next: u64,
+}
+
+impl Interner {
+    /// Returns the id for a named variable, allocating it on first use.
+    pub fn variable(&mut self, name: &str) -> VariableId {
+        if let Some(id) = self.variables.get(name) {
+            return *id;
+        }
+        let id = self.fresh();
+        self.variables.insert(name.to_owned(), id);
+        id
+    }
+
+    /// Allocates an id that is distinct from all previous ones, like for
+    /// each occurrence of `_`.
+    pub fn fresh(&mut self) -> VariableId {
+        let id = VariableId(self.next);
+        self.next += 1;
+        id
+    }
+}
+
+/// Replaces every variable in the term with a fresh one, consistently within
+/// the term, so that it shares no variables with anything else (standardizing
+/// apart).
+pub fn rename_apart(value: &Value, interner: &mut Interner) -> Value {
+    let mut renamed = HashMap::new();
+    value.map_terms(&mut |value| match value {
+        Value::Variable(id) => {
+            Value::Variable(*renamed.entry(id).or_insert_with(|| interner.fresh()))
+        }
+        value => value,
+    })
+}
+
+pub struct State {
+    pub scope: HashMap<VariableId, Value>,
+}
+
+pub struct Context {
+    pub stack: Vec<State>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub literal: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    pub kind: FormulaType,
+    pub term: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub trigger: Trigger,
+    pub context: Option<Value>,
+    pub body: Vec<Formula>,
+}
+
+/// Assembles a [`Plan`] without going through source text.
+#[derive(Debug, Clone)]
+pub struct PlanBuilder {
+    plan: Plan,
+}
+
+impl PlanBuilder {
+    pub fn new(kind: TriggerKind, literal: Value) -> PlanBuilder {
+        PlanBuilder {
+            plan: Plan {
+                trigger: Trigger { kind, literal },
+                context: None,
+                body: Vec::new(),
+            },
+        }
+    }
+
+    pub fn context(mut self, context: Value) -> PlanBuilder {
+        self.plan.context = Some(context);
+        self
+    }
+
+    pub fn formula(mut self, kind: FormulaType, term: Value) -> PlanBuilder {
+        self.plan.body.push(Formula { kind, term });
+        self
+    }
+
+    pub fn build(self) -> Plan {
+        self.plan
+    }
+}
+
+/// Builds a compound [`Value::Term`], with optional arguments and
+/// annotations, like `term!(likes(atom!(alice), var!(0))[atom!(source)])`.
+/// Arguments and annotations are arbitrary `Value` expressions.
+#[macro_export]
+macro_rules! term {
+    ($functor:ident $(($($arg:expr),* $(,)?))? $([$($annotation:expr),* $(,)?])?) => {
+        $crate::runtime::Value::Term {
+            strong_negation: false,
+            functor: stringify!($functor).to_owned(),
+            args: vec![$($($arg),*)?],
+            annotations: vec![$($($annotation),*)?],
+        }
+    };
+}
+
+/// Builds an atom, like `atom!(alice)`.
+#[macro_export]
+macro_rules! atom {
+    ($functor:ident) => {
+        $crate::runtime::Value::atom(stringify!($functor))
+    };
+}
+
+/// Builds an integer, like `int!(42)`.
+#[macro_export]
+macro_rules! int {
+    ($n:expr) => {
+        $crate::runtime::Value::Integer($n)
+    };
 }
 
-struct State {
-    scope: HashMap<VariableId, Value>,
+/// Builds a variable, either from a raw id like `var!(0)` or by name through
+/// an [`Interner`], like `var!(interner, X)`.
+///
+/// There is no `var!(X)` by name alone: the same name has to map to the same
+/// id everywhere in a value, and a single macro invocation cannot know the
+/// ids handed out by the others. `var!(X)` is parsed as a raw id expression.
+#[macro_export]
+macro_rules! var {
+    ($interner:expr, $name:ident) => {
+        $crate::runtime::Value::Variable($interner.variable(stringify!($name)))
+    };
+    ($id:expr) => {
+        $crate::runtime::Value::Variable($crate::runtime::VariableId($id))
+    };
 }
 
-struct Context {
-    stack: Vec<State>,
+/// Builds a list, like `list![int!(1), int!(2)]`, or a partial list with a
+/// tail after `;`, like `list![atom!(a); var!(0)]`.
+#[macro_export]
+macro_rules! list {
+    ($($element:expr),+ ; $tail:expr) => {
+        $crate::runtime::Value::List(Box::new($crate::runtime::List::new(
+            vec![$($element),+],
+            $crate::runtime::List::Tail($tail),
+        )))
+    };
+    ($($element:expr),* $(,)?) => {
+        $crate::runtime::Value::List(Box::new($crate::runtime::List::new(
+            vec![$($element),*],
+            $crate::runtime::List::Empty,
+        )))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Gen;
+
+    /// Generation of bounded-depth values for property tests.
+    impl Gen {
+        fn values(&mut self, depth: usize, max: u64) -> Vec<Value> {
+            (0..self.below(max + 1))
+                .map(|_| self.value(depth - 1))
+                .collect()
+        }
+
+        fn value(&mut self, depth: usize) -> Value {
+            let kinds = if depth == 0 { 5 } else { 9 };
+            match self.below(kinds) {
+                0 => Value::Integer(self.below(1000) as i64),
+                1 => Value::Float(self.below(1000) as f64 / 8.0),
+                2 => Value::String(
+                    (0..self.below(4))
+                        .map(|_| self.pick(&['a', ' ', '"', '\\', '\n', 'é']))
+                        .collect(),
+                ),
+                3 => Value::atom(self.pick(&["a", "foo", "bar_1", "true"])),
+                4 => Value::Variable(VariableId(self.below(3))),
+                5 => Value::Term {
+                    strong_negation: self.below(4) == 0,
+                    functor: self.pick(&["f", "g.h", ".print"]).to_owned(),
+                    args: self.values(depth, 3),
+                    annotations: self.values(depth, 2),
+                },
+                6 => {
+                    let elements = self.values(depth, 3);
+                    let tail = match self.below(3) {
+                        0 => List::Tail(Value::Variable(VariableId(self.below(3)))),
+                        _ => List::Empty,
+                    };
+                    Value::List(Box::new(List::new(elements, tail)))
+                }
+                7 => Value::UnaryOp {
+                    op: self.pick(&[UnaryOparator::Pos, UnaryOparator::Neg, UnaryOparator::Not]),
+                    value: Box::new(self.value(depth - 1)),
+                },
+                _ => Value::BinaryOp {
+                    op: self.pick(&[
+                        BinaryOperator::Or,
+                        BinaryOperator::And,
+                        BinaryOperator::LtEq,
+                        BinaryOperator::NotEqual,
+                        BinaryOperator::Decompose,
+                        BinaryOperator::ArithEqual,
+                        BinaryOperator::Eq,
+                        BinaryOperator::Gt,
+                        BinaryOperator::Plus,
+                        BinaryOperator::Minus,
+                        BinaryOperator::Div,
+                        BinaryOperator::FloorDiv,
+                        BinaryOperator::Pow,
+                    ]),
+                    left: Box::new(self.value(depth - 1)),
+                    right: Box::new(self.value(depth - 1)),
+                },
+            }
+        }
+    }
+
+    fn samples(n: usize) -> Vec<Value> {
+        let mut gen = Gen::new(0x9e37_79b9_7f4a_7c15);
+        (0..n).map(|_| gen.value(4)).collect()
+    }
+
+    #[test]
+    fn test_prop_display_round_trip() {
+        let ground: Vec<_> = samples(2000).into_iter().filter(Value::is_ground).collect();
+        assert!(ground.len() > 200);
+        for value in ground {
+            let printed = value.to_string();
+            let parsed =
+                Value::parse(&printed).unwrap_or_else(|err| panic!("{}: {:?}", printed, err));
+            assert_eq!(parsed, value, "{}", printed);
+        }
+    }
+
+    #[test]
+    fn test_prop_standard_order() {
+        let values = samples(60);
+        for a in &values {
+            assert_eq!(a.standard_cmp(&a.clone()), Ordering::Equal);
+            assert_eq!(a.structural_hash(false), a.clone().structural_hash(false));
+            for b in &values {
+                let ab = a.standard_cmp(b);
+                assert_eq!(ab, b.standard_cmp(a).reverse(), "{} vs {}", a, b);
+                if ab == Ordering::Equal {
+                    assert_eq!(a, b);
+                }
+                for c in &values {
+                    if ab != Ordering::Greater && b.standard_cmp(c) != Ordering::Greater {
+                        assert_ne!(a.standard_cmp(c), Ordering::Greater, "{} {} {}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+
+    fn term(functor: &str, args: Vec<Value>) -> Value {
+        Value::Term {
+            strong_negation: false,
+            functor: functor.to_owned(),
+            args,
+            annotations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        let value: Value = "foo(1)".parse().unwrap();
+        assert_eq!(value, term("foo", vec![Value::Integer(1)]));
+        assert!("foo(".parse::<Value>().is_err());
+
+        assert_eq!(
+            Value::parse_ground("foo(1, [a, \"b\"])[source(self)]").unwrap(),
+            Value::parse("foo(1, [a, \"b\"])[source(self)]").unwrap()
+        );
+        assert!(matches!(
+            Value::parse_ground("foo(X)"),
+            Err(PheresError::NotGround)
+        ));
+        assert!(matches!(
+            Value::parse_ground("[a | T]"),
+            Err(PheresError::NotGround)
+        ));
+        assert!(matches!(
+            Value::parse_ground("foo(1) bar"),
+            Err(PheresError::Parser(_))
+        ));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let source_self = term("source", vec![Value::atom("self")]);
+        let belief = Value::atom("at").with_annotation(source_self.clone());
+        assert_eq!(belief, Value::parse("at[source(self)]").unwrap());
+        assert_eq!(belief.annotations(), std::slice::from_ref(&source_self));
+
+        let belief = belief.with_annotation(source_self.clone());
+        assert_eq!(belief.annotations().len(), 1);
+
+        let belief = belief.with_annotation(Value::atom("expires"));
+        let belief = belief.without_annotation(&source_self);
+        assert_eq!(belief, Value::parse("at[expires]").unwrap());
+
+        assert!(Value::Integer(1).annotations().is_empty());
+        assert_eq!(
+            Value::Integer(1).with_annotation(source_self.clone()),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            Value::from("x").without_annotation(&source_self),
+            Value::from("x")
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Value::from(42).as_i64(), Some(42));
+        assert_eq!(Value::from(42).as_f64(), Some(42.0));
+        assert_eq!(Value::from(1.5).as_f64(), Some(1.5));
+        assert_eq!(Value::from(1.5).as_i64(), None);
+        assert_eq!(Value::from("hi").as_str(), Some("hi"));
+        assert_eq!(Value::from(String::from("hi")), Value::from("hi"));
+        assert_eq!(Value::from("hi").as_i64(), None);
+        assert_eq!(Value::from(7).as_str(), None);
+        assert_eq!(Value::from(true), Value::parse("true").unwrap());
+        assert_eq!(Value::from(false), Value::atom("false"));
+        assert_eq!(Value::atom("hi").as_str(), None);
+    }
+
+    #[test]
+    fn test_depth_and_size() {
+        let nested = term(
+            "f",
+            vec![term("g", vec![term("h", vec![Value::atom("a")])])],
+        );
+        assert_eq!(nested.depth(), 4);
+        assert_eq!(nested.size(), 4);
+
+        let flat = term(
+            "f",
+            vec![Value::atom("a"), Value::atom("b"), Value::atom("c")],
+        );
+        assert_eq!(flat.depth(), 2);
+        assert_eq!(flat.size(), 4);
+
+        let open = Value::parse("[a | T]").unwrap();
+        assert_eq!(open.depth(), 2);
+        assert_eq!(open.size(), 3);
+
+        let nested_tail = Value::parse("[a | [b]]").unwrap();
+        assert_eq!(nested_tail.depth(), 3);
+        assert_eq!(nested_tail.size(), 4);
+    }
+
+    #[test]
+    fn test_occurs() {
+        let x = VariableId(0);
+        let y = VariableId(1);
+        let value = Value::parse("f(X)").unwrap();
+        assert!(value.occurs(x));
+        assert!(!value.occurs(y));
+
+        let value = Value::parse("f(a, [b | T])[source(S)]").unwrap();
+        assert!(value.occurs(VariableId(0)));
+        assert!(value.occurs(VariableId(1)));
+        assert!(!value.occurs(VariableId(2)));
+        assert!(Value::parse("-(1 + X)").unwrap().occurs(x));
+        assert!(!Value::atom("a").occurs(x));
+    }
+
+    #[test]
+    fn test_long_list() {
+        let n = 1_000_000;
+        let list = List::new((0..n).map(Value::Integer).collect(), List::Empty);
+        assert_eq!(list.len(), n as usize);
+
+        let reversed = list.reverse();
+        assert_eq!(reversed.len(), n as usize);
+        assert_eq!(reversed.iter().next(), Some(&Value::Integer(n - 1)));
+        assert_ne!(reversed, list);
+        assert_eq!(reversed.reverse(), list);
+
+        let doubled = list.concat(&list.clone());
+        assert_eq!(doubled.len(), 2 * n as usize);
+        assert_eq!(doubled.to_vec()[n as usize], Value::Integer(0));
+    }
+
+    #[test]
+    fn test_list_operations() {
+        let list = |source: &str| match Value::parse(source).unwrap() {
+            Value::List(list) => *list,
+            _ => panic!("expected list"),
+        };
+
+        assert_eq!(list("[a, b, c]").reverse(), list("[c, b, a]"));
+        assert_eq!(list("[a, b | T]").reverse(), list("[b, a | T]"));
+        assert_eq!(list("[a | T]").concat(&list("[b, c]")), list("[a, b, c]"));
+        assert_eq!(list("[]").len(), 0);
+        assert!(list("[]").is_empty());
+        assert_eq!(list("[a, [b, c] | T]").to_vec().len(), 2);
+    }
+
+    #[test]
+    fn test_parse() {
+        let value = Value::parse("f(1, [a|T])").unwrap();
+        let Value::Term {
+            strong_negation,
+            functor,
+            args,
+            annotations,
+        } = value
+        else {
+            panic!("expected term");
+        };
+        assert!(!strong_negation);
+        assert_eq!(functor, "f");
+        assert!(annotations.is_empty());
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0], Value::Integer(1));
+        assert!(matches!(
+            &args[1],
+            Value::List(list) if matches!(
+                &**list,
+                List::Element { head, tail }
+                    if *head == Value::atom("a")
+                    && matches!(&**tail, List::Tail(Value::Variable(_)))
+            )
+        ));
+    }
+
+    #[test]
+    fn test_strong_negation() {
+        use crate::{ast::Item, parser::parse};
+
+        let strong = Value::parse("~busy").unwrap();
+        assert!(matches!(
+            &strong,
+            Value::Term { strong_negation: true, functor, .. } if functor == "busy"
+        ));
+        assert_ne!(strong, Value::atom("busy"));
+        assert_ne!(strong, Value::parse("not busy").unwrap());
+        assert!(matches!(
+            Value::parse("not busy").unwrap(),
+            Value::UnaryOp {
+                op: UnaryOparator::Not,
+                ..
+            }
+        ));
+        assert_eq!(strong.to_string(), "~busy");
+
+        let lexed = LexedStr::new("+~busy(X) <- relax.");
+        let parsed = parse(&lexed);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        let Some(Item::Plan(plan)) = parsed.root().items().next() else {
+            panic!("expected plan");
+        };
+        let trigger = lower_term(
+            &Term::Literal(plan.trigger().unwrap()),
+            &mut Interner::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            trigger,
+            Value::Term { strong_negation: true, ref args, .. } if args.len() == 1
+        ));
+        assert_eq!(trigger.to_string(), "~busy(_V0)");
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(matches!(
+            Value::parse("f(1,"),
+            Err(PheresError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            Value::parse("f(1) g"),
+            Err(PheresError::Parser(_))
+        ));
+        assert!(matches!(Value::parse(""), Err(PheresError::UnexpectedEof)));
+        assert!(matches!(
+            Value::parse("  // nothing"),
+            Err(PheresError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_term_macros() {
+        let mut interner = Interner::default();
+        let built = term!(
+            likes(
+                atom!(alice),
+                var!(interner, X),
+                list![int!(1), term!(f(var!(interner, X))); var!(interner, T)],
+                list![],
+            )[term!(source(atom!(self)))]
+        );
+        assert_eq!(
+            built,
+            Value::Term {
+                strong_negation: false,
+                functor: "likes".to_owned(),
+                args: vec![
+                    Value::atom("alice"),
+                    Value::Variable(VariableId(0)),
+                    Value::List(Box::new(List::new(
+                        vec![
+                            Value::Integer(1),
+                            Value::Term {
+                                strong_negation: false,
+                                functor: "f".to_owned(),
+                                args: vec![Value::Variable(VariableId(0))],
+                                annotations: Vec::new(),
+                            },
+                        ],
+                        List::Tail(Value::Variable(VariableId(1))),
+                    ))),
+                    Value::List(Box::new(List::Empty)),
+                ],
+                annotations: vec![Value::parse("source(self)").unwrap()],
+            }
+        );
+        assert_eq!(
+            built,
+            Value::parse("likes(alice, X, [1, f(X) | T], [])[source(self)]").unwrap()
+        );
+        assert_eq!(term!(foo), Value::atom("foo"));
+        assert_eq!(var!(7), Value::Variable(VariableId(7)));
+    }
+
+    #[test]
+    fn test_structural_hash() {
+        let a = Value::parse("foo(1, bar(\"x\"), [a, b])[source(self)]").unwrap();
+        let b = Value::parse("foo(1, bar(\"x\"), [a, b])[source(self)]").unwrap();
+        assert_eq!(a.structural_hash(false), b.structural_hash(false));
+
+        let different = Value::parse("foo(1, bar(\"y\"), [a, b])[source(self)]").unwrap();
+        assert_ne!(a.structural_hash(false), different.structural_hash(false));
+        let different = Value::parse("foo(1, bar(\"x\"), [b, a])[source(self)]").unwrap();
+        assert_ne!(a.structural_hash(false), different.structural_hash(false));
+
+        let annotated = Value::parse("foo(1, bar(\"x\"), [a, b])[source(percept)]").unwrap();
+        assert_ne!(a.structural_hash(false), annotated.structural_hash(false));
+        assert_eq!(a.structural_hash(true), annotated.structural_hash(true));
+    }
+
+    #[test]
+    fn test_eq_ignoring_annotations() {
+        let eq = |a: &str, b: &str| {
+            Value::parse(a)
+                .unwrap()
+                .eq_ignoring_annotations(&Value::parse(b).unwrap())
+        };
+        assert!(eq("foo[source(self)]", "foo[source(percept)]"));
+        assert!(eq("f(a[x], [b[y]])", "f(a, [b])"));
+        assert!(!eq("foo[a]", "bar[a]"));
+        assert!(!eq("f(a)", "f(a, b)"));
+        assert!(!eq("~foo", "foo"));
+        assert!(!eq("[a, b]", "[a]"));
+        assert!(!eq("[a | T]", "[a]"));
+        assert!(eq("1 + x[a]", "1 + x"));
+
+        let mut gen = Gen::new(7);
+        for _ in 0..200 {
+            let value = gen.value(3);
+            assert!(value.eq_ignoring_annotations(&value.clone()));
+        }
+    }
+
+    #[test]
+    fn test_standard_cmp() {
+        let mut values = [
+            Value::parse("f(a, b)").unwrap(),
+            Value::parse("\"str\"").unwrap(),
+            Value::parse("g(a)").unwrap(),
+            Value::Integer(42),
+            Value::parse("X").unwrap(),
+            Value::Float(1.5),
+        ];
+        values.sort_by(Value::standard_cmp);
+        assert!(matches!(values[0], Value::Variable(_)));
+        assert_eq!(values[1], Value::Float(1.5));
+        assert_eq!(values[2], Value::Integer(42));
+        assert_eq!(values[3], Value::String("str".to_owned()));
+        assert_eq!(values[4], Value::parse("g(a)").unwrap());
+        assert_eq!(values[5], Value::parse("f(a, b)").unwrap());
+
+        assert_eq!(
+            Value::parse("f(a, b)")
+                .unwrap()
+                .standard_cmp(&Value::parse("f(a, c)").unwrap()),
+            Ordering::Less
+        );
+        assert_eq!(
+            Value::parse("g(a)")
+                .unwrap()
+                .standard_cmp(&Value::parse("f(a)").unwrap()),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Value::Float(1.0).standard_cmp(&Value::Integer(1)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_map_terms() {
+        let value = Value::parse("f(1, g([2, h(3)], X), 4 + 5)").unwrap();
+        let incremented = value.map_terms(&mut |value| match value {
+            Value::Integer(n) => Value::Integer(n + 1),
+            value => value,
+        });
+        let Value::Term { args, .. } = &value else {
+            panic!("expected term");
+        };
+        let Value::Term { args: inner, .. } = &args[1] else {
+            panic!("expected term");
+        };
+        let x = inner[1].clone();
+        assert_eq!(
+            incremented,
+            term(
+                "f",
+                vec![
+                    Value::Integer(2),
+                    term(
+                        "g",
+                        vec![
+                            Value::List(Box::new(List::new(
+                                vec![Value::Integer(3), term("h", vec![Value::Integer(4)])],
+                                List::Empty
+                            ))),
+                            x,
+                        ]
+                    ),
+                    Value::BinaryOp {
+                        op: BinaryOperator::Plus,
+                        left: Box::new(Value::Integer(5)),
+                        right: Box::new(Value::Integer(6)),
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_rename_apart() {
+        let mut interner = Interner::default();
+        let x = interner.variable("X");
+        let y = interner.variable("Y");
+
+        // f(X, g(X, Y))
+        let value = term(
+            "f",
+            vec![
+                Value::Variable(x),
+                term("g", vec![Value::Variable(x), Value::Variable(y)]),
+            ],
+        );
+
+        let renamed = rename_apart(&value, &mut interner);
+        let Value::Term { args, .. } = &renamed else {
+            panic!("expected term");
+        };
+        let Value::Variable(x1) = args[0] else {
+            panic!("expected variable");
+        };
+        let Value::Term { args: inner, .. } = &args[1] else {
+            panic!("expected term");
+        };
+        let (Value::Variable(x2), Value::Variable(y1)) = (&inner[0], &inner[1]) else {
+            panic!("expected variables");
+        };
+
+        assert_eq!(x1, *x2);
+        assert_ne!(x1, *y1);
+        assert_ne!(x1, x);
+        assert_ne!(x1, y);
+        assert_ne!(*y1, x);
+        assert_ne!(*y1, y);
+    }
+
+    #[test]
+    fn test_plan_builder() {
+        // +!go : ready <- act; +done.
+        let plan = PlanBuilder::new(TriggerKind::AchieveAdd, Value::atom("go"))
+            .context(Value::atom("ready"))
+            .formula(FormulaType::Term, Value::atom("act"))
+            .formula(FormulaType::Add, Value::atom("done"))
+            .build();
+
+        assert_eq!(
+            plan,
+            Plan {
+                trigger: Trigger {
+                    kind: TriggerKind::AchieveAdd,
+                    literal: Value::atom("go"),
+                },
+                context: Some(Value::atom("ready")),
+                body: vec![
+                    Formula {
+                        kind: FormulaType::Term,
+                        term: Value::atom("act"),
+                    },
+                    Formula {
+                        kind: FormulaType::Add,
+                        term: Value::atom("done"),
+                    },
+                ],
+            }
+        );
+    }
 }
@@ -1,8 +1,11 @@
-use std::{fmt, ops::Range};
+use std::{
+    fmt::{self, Write as _},
+    ops::Range,
+};
 
-use rowan::Language;
+use rowan::{Language, NodeOrToken, TextRange};
 
-use crate::lexer::{tokenize, TokenKind};
+use crate::lexer::{tokenize_with_options, LexerOptions, TokenKind};
 
 #[derive(Copy, Clone, Debug)]
 pub struct TokenIdx(usize);
@@ -20,6 +23,7 @@ pub enum SyntaxKind {
     Whitespace,
     LineComment,
     BlockComment,
+    Shebang,
 
     Functor,
     Variable,
@@ -79,9 +83,13 @@ pub enum SyntaxKind {
     NotEqual,
     Equal,
     Decompose,
+    ArithEqual,
+    ArithNotEqual,
     Eq,
     Lt,
     Gt,
+    ShiftLeft,
+    ShiftRight,
 
     Semi,
     Comma,
@@ -89,12 +97,18 @@ pub enum SyntaxKind {
     At,
 
     Error,
+    /// Empty node where a construct was expected but is absent, for
+    /// example after a trailing `&`.
+    Missing,
     Eof,
 
     Belief,
     Rule,
     InitialGoal,
+    /// A braced directive, like `{ include("lib.asl") }`.
+    Directive,
     Plan,
+    PlanGroup,
     PlanAnnotation,
     PlanContext,
     Body,
@@ -106,6 +120,9 @@ pub enum SyntaxKind {
     Conjunction,
     Negation,
     Comparison,
+    /// `X << 1` or `X >> 1`, which is rejected by the parser but kept as a
+    /// node of its own.
+    ShiftExpression,
     AdditiveExpression,
     MultiplicativeExpression,
     UnaryExpression,
@@ -113,6 +130,8 @@ pub enum SyntaxKind {
     Exponentiation,
     Atom,
     List,
+    /// The `| T` at the end of a partial list.
+    ListTail,
     WhileLoop,
     ForLoop,
     IfThenElse,
@@ -120,6 +139,60 @@ pub enum SyntaxKind {
 }
 
 impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Whitespace
+                | SyntaxKind::LineComment
+                | SyntaxKind::BlockComment
+                | SyntaxKind::Shebang
+        )
+    }
+
+    /// Nodes that form terms, including literals and lists.
+    pub fn is_expression(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Literal
+                | SyntaxKind::LiteralTerms
+                | SyntaxKind::LiteralAnnotations
+                | SyntaxKind::Disjunction
+                | SyntaxKind::Conjunction
+                | SyntaxKind::Negation
+                | SyntaxKind::Comparison
+                | SyntaxKind::ShiftExpression
+                | SyntaxKind::AdditiveExpression
+                | SyntaxKind::MultiplicativeExpression
+                | SyntaxKind::UnaryExpression
+                | SyntaxKind::PowerExpression
+                | SyntaxKind::Exponentiation
+                | SyntaxKind::Atom
+                | SyntaxKind::List
+                | SyntaxKind::ListTail
+        )
+    }
+
+    /// Nodes that make up the program structure around terms.
+    pub fn is_structural(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Root
+                | SyntaxKind::Belief
+                | SyntaxKind::Rule
+                | SyntaxKind::InitialGoal
+                | SyntaxKind::Directive
+                | SyntaxKind::Plan
+                | SyntaxKind::PlanGroup
+                | SyntaxKind::PlanAnnotation
+                | SyntaxKind::PlanContext
+                | SyntaxKind::Body
+                | SyntaxKind::Formula
+                | SyntaxKind::WhileLoop
+                | SyntaxKind::ForLoop
+                | SyntaxKind::IfThenElse
+        )
+    }
+
     pub fn comparison_operator(self) -> Option<ComparisonOperator> {
         Some(match self {
             SyntaxKind::LtEq => ComparisonOperator::LtEq,
@@ -127,6 +200,8 @@ impl SyntaxKind {
             SyntaxKind::NotEqual => ComparisonOperator::NotEqual,
             SyntaxKind::Equal => ComparisonOperator::Equal,
             SyntaxKind::Decompose => ComparisonOperator::Decompose,
+            SyntaxKind::ArithEqual => ComparisonOperator::ArithEqual,
+            SyntaxKind::ArithNotEqual => ComparisonOperator::ArithNotEqual,
             SyntaxKind::Eq => ComparisonOperator::Eq,
             SyntaxKind::Lt => ComparisonOperator::Lt,
             SyntaxKind::Gt => ComparisonOperator::Gt,
@@ -171,19 +246,255 @@ impl SyntaxKind {
             _ => return None,
         })
     }
+
+    /// Tokens that the grammar allows directly after a token of this kind,
+    /// for completion. This is an over-approximation where a token is
+    /// ambiguous, like `+` as a trigger or an operator.
+    pub fn expected_after(self) -> &'static [SyntaxKind] {
+        use SyntaxKind::*;
+
+        const TERM_START: &[SyntaxKind] = &[
+            Functor,
+            Variable,
+            Wildcard,
+            Integer,
+            Float,
+            String,
+            True,
+            False,
+            Tilde,
+            Not,
+            Plus,
+            Minus,
+            OpenParen,
+            OpenBracket,
+        ];
+        const FORMULA_START: &[SyntaxKind] = &[
+            Bang,
+            BangBang,
+            Question,
+            Plus,
+            Minus,
+            MinusPlus,
+            While,
+            If,
+            For,
+            Functor,
+            Variable,
+            Wildcard,
+            Integer,
+            Float,
+            String,
+            True,
+            False,
+            Tilde,
+            Not,
+            OpenParen,
+            OpenBracket,
+        ];
+        const ITEM_START: &[SyntaxKind] =
+            &[Functor, Tilde, Bang, Plus, Minus, At, Begin, End, OpenBrace];
+        const TERM_END: &[SyntaxKind] = &[
+            Plus,
+            Minus,
+            Star,
+            Slash,
+            Div,
+            Mod,
+            Pow,
+            And,
+            Or,
+            LtEq,
+            GtEq,
+            NotEqual,
+            Equal,
+            Decompose,
+            ArithEqual,
+            ArithNotEqual,
+            Eq,
+            Lt,
+            Gt,
+            Colon,
+            Arrow,
+            Define,
+            Comma,
+            Semi,
+            Dot,
+            CloseParen,
+            CloseBracket,
+            CloseBrace,
+        ];
+        // After the arguments of a literal, which may be followed by
+        // annotations.
+        const ARGUMENTS_END: &[SyntaxKind] = &[
+            OpenBracket,
+            Plus,
+            Minus,
+            Star,
+            Slash,
+            Div,
+            Mod,
+            Pow,
+            And,
+            Or,
+            LtEq,
+            GtEq,
+            NotEqual,
+            Equal,
+            Decompose,
+            ArithEqual,
+            ArithNotEqual,
+            Eq,
+            Lt,
+            Gt,
+            Colon,
+            Arrow,
+            Define,
+            Comma,
+            Semi,
+            Dot,
+            CloseParen,
+            CloseBracket,
+            CloseBrace,
+        ];
+        const LITERAL_END: &[SyntaxKind] = &[
+            OpenParen,
+            OpenBracket,
+            Plus,
+            Minus,
+            Star,
+            Slash,
+            Div,
+            Mod,
+            Pow,
+            And,
+            Or,
+            LtEq,
+            GtEq,
+            NotEqual,
+            Equal,
+            Decompose,
+            ArithEqual,
+            ArithNotEqual,
+            Eq,
+            Lt,
+            Gt,
+            Colon,
+            Arrow,
+            Define,
+            Comma,
+            Semi,
+            Dot,
+            CloseParen,
+            CloseBracket,
+            CloseBrace,
+        ];
+
+        match self {
+            Arrow | Semi => FORMULA_START,
+            Dot => ITEM_START,
+            Functor => LITERAL_END,
+            Variable | Wildcard | Integer | Float | String | True | False | CloseBracket => {
+                TERM_END
+            }
+            CloseParen => ARGUMENTS_END,
+            Colon | Define | Comma | OpenParen | Not | And | Or | Star | Slash | Div | Mod
+            | Pow | LtEq | GtEq | NotEqual | Equal | Decompose | ArithEqual | ArithNotEqual
+            | Eq | Lt | Gt => TERM_START,
+            OpenBracket => &[
+                Functor,
+                Variable,
+                Wildcard,
+                Integer,
+                Float,
+                String,
+                True,
+                False,
+                Tilde,
+                Not,
+                Plus,
+                Minus,
+                OpenParen,
+                OpenBracket,
+                CloseBracket,
+            ],
+            Plus | Minus => &[
+                Bang,
+                Question,
+                Functor,
+                Variable,
+                Wildcard,
+                Integer,
+                Float,
+                String,
+                True,
+                False,
+                Tilde,
+                Not,
+                Plus,
+                Minus,
+                OpenParen,
+                OpenBracket,
+            ],
+            Bang | BangBang | Question => &[Functor, Variable, Tilde],
+            MinusPlus | Tilde | ColonColon => &[Functor],
+            At => &[Functor, OpenBracket],
+            // A directive or a block in a plan body.
+            OpenBrace => &[
+                Include,
+                Begin,
+                End,
+                Bang,
+                BangBang,
+                Question,
+                Plus,
+                Minus,
+                MinusPlus,
+                While,
+                If,
+                For,
+                Functor,
+                Variable,
+                Wildcard,
+                Integer,
+                Float,
+                String,
+                True,
+                False,
+                Tilde,
+                Not,
+                OpenParen,
+                OpenBracket,
+                CloseBrace,
+            ],
+            Include => &[OpenParen, CloseBrace],
+            CloseBrace => &[
+                Functor, Tilde, Bang, Plus, Minus, At, Begin, End, OpenBrace, Semi, Dot,
+                CloseBrace, Else, Elif,
+            ],
+            While | If | Elif | For => &[OpenParen],
+            Else => &[OpenBrace, If],
+            Begin | End => ITEM_START,
+            _ => &[],
+        }
+    }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ComparisonOperator {
     LtEq,
     GtEq,
     NotEqual,
     Equal,
     Decompose,
+    ArithEqual,
+    ArithNotEqual,
     Eq,
     Lt,
     Gt,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum FormulaType {
     AchieveLater,
     Achieve,
@@ -194,11 +505,21 @@ pub enum FormulaType {
     Term,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TriggerKind {
+    BeliefAdd,
+    BeliefDel,
+    AchieveAdd,
+    AchieveDel,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AdditiveOperator {
     Add,
     Sub,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MultiplicativeOperator {
     Mul,
     Div,
@@ -206,6 +527,7 @@ pub enum MultiplicativeOperator {
     Mod,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum UnaryOperator {
     Pos,
     Neg,
@@ -231,12 +553,168 @@ impl Language for AgentSpeakLanguage {
     }
 }
 
+/// Range of a node without leading and trailing trivia, which the parser
+/// attaches to whichever node is open.
+pub fn trimmed_range(node: &SyntaxNode) -> TextRange {
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(|element| element.into_token())
+        .filter(|token| !token.kind().is_trivia());
+    match tokens.next() {
+        Some(first) => {
+            let end = tokens
+                .last()
+                .unwrap_or_else(|| first.clone())
+                .text_range()
+                .end();
+            TextRange::new(first.text_range().start(), end)
+        }
+        None => node.text_range(),
+    }
+}
+
+/// The contents of a string token, without quotes and with escape
+/// sequences resolved.
+pub(crate) fn unescape(text: &str) -> String {
+    let inner = text.strip_prefix('"').unwrap_or(text);
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some('r') => res.push('\r'),
+                Some(other) => res.push(other),
+                None => (),
+            }
+        } else {
+            res.push(ch);
+        }
+    }
+    res
+}
+
+/// Concatenates the text of all tokens, including trivia. Lexing is
+/// lossless, so this is always the original input.
+pub fn reconstruct(lexed: &LexedStr<'_>) -> String {
+    lexed.iter().map(|(_, text)| text).collect()
+}
+
+/// Renders a syntax tree with one indented line per node or token, as
+/// printed by the command line tool and used by the golden tests.
+pub fn debug_tree(node: &SyntaxNode) -> String {
+    fn write_element(out: &mut String, level: usize, element: SyntaxElement) {
+        let kind = element.kind();
+        let _ = write!(out, "{:indent$}", "", indent = level * 2);
+        match element {
+            NodeOrToken::Node(node) => {
+                let _ = writeln!(out, "- {:?}", kind);
+                for child in node.children_with_tokens() {
+                    write_element(out, level + 1, child);
+                }
+            }
+            NodeOrToken::Token(token) => {
+                let _ = writeln!(out, "- {:?} {:?}", token.text(), kind);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_element(&mut out, 0, node.clone().into());
+    out
+}
+
+/// Renders all tokens, one per line, with the kind, byte range and quoted
+/// text in aligned columns.
+pub fn token_table(lexed: &LexedStr<'_>) -> String {
+    let rows: Vec<(String, String, String)> = (0..lexed.len())
+        .map(|idx| {
+            let range = lexed.token_range(TokenIdx(idx));
+            (
+                format!("{:?}", lexed.kind[idx]),
+                format!("{}..{}", range.start, range.end),
+                format!("{:?}", &lexed.text[range]),
+            )
+        })
+        .collect();
+
+    let kind_width = rows
+        .iter()
+        .map(|(kind, _, _)| kind.len())
+        .max()
+        .unwrap_or(0);
+    let range_width = rows
+        .iter()
+        .map(|(_, range, _)| range.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for (kind, range, text) in rows {
+        let _ = writeln!(
+            out,
+            "{:kind_width$}  {:range_width$}  {}",
+            kind, range, text
+        );
+    }
+    out
+}
+
+/// Renders a syntax tree as a single-line S-expression like
+/// `(Root (Belief (Literal foo) .))`, leaving out trivia. Tokens that contain
+/// parentheses or whitespace are quoted.
+pub fn sexpr_tree(node: &SyntaxNode) -> String {
+    fn write_element(out: &mut String, element: SyntaxElement) {
+        match element {
+            NodeOrToken::Node(node) => {
+                let _ = write!(out, "({:?}", node.kind());
+                for child in node.children_with_tokens() {
+                    if !child.kind().is_trivia() {
+                        out.push(' ');
+                        write_element(out, child);
+                    }
+                }
+                out.push(')');
+            }
+            NodeOrToken::Token(token) => {
+                let text = token.text();
+                if text.is_empty()
+                    || text.contains(|ch: char| ch == '(' || ch == ')' || ch.is_whitespace())
+                {
+                    let _ = write!(out, "{:?}", text);
+                } else {
+                    out.push_str(text);
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    write_element(&mut out, node.clone().into());
+    out
+}
+
 #[derive(Debug)]
 pub struct SyntaxError {
     pub kind: SyntaxErrorKind,
     pub token_idx: TokenIdx,
 }
 
+impl SyntaxError {
+    /// The error message with its location, like
+    /// `unterminated string at byte 8`.
+    pub fn describe(&self, lexed: &LexedStr<'_>) -> String {
+        format!(
+            "{} at byte {}",
+            self.kind,
+            lexed.token_range(self.token_idx).start
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum SyntaxErrorKind {
     UnterminatedBlockComment,
@@ -254,6 +732,119 @@ impl fmt::Display for SyntaxErrorKind {
     }
 }
 
+/// Maps a lexer token to its syntax kind and the error it represents, if any.
+fn convert(kind: TokenKind) -> (SyntaxKind, Option<SyntaxErrorKind>) {
+    let mut error = None;
+    let syntax_kind = match kind {
+        TokenKind::Whitespace => SyntaxKind::Whitespace,
+        TokenKind::LineComment => SyntaxKind::LineComment,
+        TokenKind::Shebang => SyntaxKind::Shebang,
+        TokenKind::BlockComment { terminated } => {
+            if !terminated {
+                error = Some(SyntaxErrorKind::UnterminatedBlockComment);
+            }
+            SyntaxKind::BlockComment
+        }
+
+        TokenKind::Functor => SyntaxKind::Functor,
+        TokenKind::Variable => SyntaxKind::Variable,
+        TokenKind::Wildcard => SyntaxKind::Wildcard,
+        TokenKind::Integer => SyntaxKind::Integer,
+        TokenKind::Float => SyntaxKind::Float,
+        TokenKind::String { terminated } => {
+            if !terminated {
+                error = Some(SyntaxErrorKind::UnterminatedString);
+            }
+            SyntaxKind::String
+        }
+
+        TokenKind::True => SyntaxKind::True,
+        TokenKind::False => SyntaxKind::False,
+
+        TokenKind::If => SyntaxKind::If,
+        TokenKind::Else => SyntaxKind::Else,
+        TokenKind::Elif => SyntaxKind::Elif,
+        TokenKind::While => SyntaxKind::While,
+        TokenKind::For => SyntaxKind::For,
+
+        TokenKind::Include => SyntaxKind::Include,
+        TokenKind::Begin => SyntaxKind::Begin,
+        TokenKind::End => SyntaxKind::End,
+
+        TokenKind::OpenParen => SyntaxKind::OpenParen,
+        TokenKind::CloseParen => SyntaxKind::CloseParen,
+        TokenKind::OpenBracket => SyntaxKind::OpenBracket,
+        TokenKind::CloseBracket => SyntaxKind::CloseBracket,
+        TokenKind::OpenBrace => SyntaxKind::OpenBrace,
+        TokenKind::CloseBrace => SyntaxKind::CloseBrace,
+
+        TokenKind::Arrow => SyntaxKind::Arrow,
+        TokenKind::ColonArrow => SyntaxKind::ColonArrow,
+        TokenKind::Define => SyntaxKind::Define,
+        TokenKind::Colon => SyntaxKind::Colon,
+        TokenKind::ColonColon => SyntaxKind::ColonColon,
+
+        TokenKind::ForkJoinAnd => SyntaxKind::ForkJoinAnd,
+        TokenKind::ForkJoinXor => SyntaxKind::ForkJoinXor,
+
+        TokenKind::BangBang => SyntaxKind::BangBang,
+        TokenKind::Bang => SyntaxKind::Bang,
+        TokenKind::Question => SyntaxKind::Question,
+        TokenKind::MinusPlus => SyntaxKind::MinusPlus,
+
+        TokenKind::Not => SyntaxKind::Not,
+        TokenKind::Tilde => SyntaxKind::Tilde,
+        TokenKind::Plus => SyntaxKind::Plus,
+        TokenKind::Minus => SyntaxKind::Minus,
+        TokenKind::Slash => SyntaxKind::Slash,
+        TokenKind::Div => SyntaxKind::Div,
+        TokenKind::Mod => SyntaxKind::Mod,
+        TokenKind::Pow => SyntaxKind::Pow,
+        TokenKind::Star => SyntaxKind::Star,
+        TokenKind::And => SyntaxKind::And,
+        TokenKind::Or => SyntaxKind::Or,
+
+        TokenKind::LtEq => SyntaxKind::LtEq,
+        TokenKind::GtEq => SyntaxKind::GtEq,
+        TokenKind::NotEqual => SyntaxKind::NotEqual,
+        TokenKind::Equal => SyntaxKind::Equal,
+        TokenKind::Decompose => SyntaxKind::Decompose,
+        TokenKind::ArithEqual => SyntaxKind::ArithEqual,
+        TokenKind::ArithNotEqual => SyntaxKind::ArithNotEqual,
+        TokenKind::Eq => SyntaxKind::Eq,
+        TokenKind::Lt => SyntaxKind::Lt,
+        TokenKind::Gt => SyntaxKind::Gt,
+        TokenKind::ShiftLeft => SyntaxKind::ShiftLeft,
+        TokenKind::ShiftRight => SyntaxKind::ShiftRight,
+
+        TokenKind::Semi => SyntaxKind::Semi,
+        TokenKind::Comma => SyntaxKind::Comma,
+        TokenKind::Dot => SyntaxKind::Dot,
+        TokenKind::At => SyntaxKind::At,
+
+        TokenKind::Unknown => {
+            error = Some(SyntaxErrorKind::UnexpectedToken);
+            SyntaxKind::Error
+        }
+    };
+    (syntax_kind, error)
+}
+
+/// Tokens of `text` with their byte ranges, including trivia, lexed on
+/// demand without building a [`LexedStr`]. Kinds and ranges are the same,
+/// but lex errors only show up as token kinds.
+pub fn stream_tokens<'a>(
+    text: &'a str,
+    options: &LexerOptions,
+) -> impl Iterator<Item = (SyntaxKind, Range<usize>, &'a str)> + 'a {
+    let mut offset = 0;
+    tokenize_with_options(text, options).map(move |token| {
+        let range = offset..offset + token.len;
+        offset = range.end;
+        (convert(token.kind).0, range.clone(), &text[range])
+    })
+}
+
 #[derive(Debug)]
 pub struct LexedStr<'a> {
     pub text: &'a str,
@@ -264,6 +855,10 @@ pub struct LexedStr<'a> {
 
 impl LexedStr<'_> {
     pub fn new(text: &str) -> LexedStr<'_> {
+        LexedStr::with_options(text, &LexerOptions::default())
+    }
+
+    pub fn with_options<'a>(text: &'a str, options: &LexerOptions) -> LexedStr<'a> {
         let mut res = LexedStr {
             text,
             kind: Vec::new(),
@@ -273,103 +868,14 @@ impl LexedStr<'_> {
 
         let mut offset = 0;
 
-        for token in tokenize(text) {
-            let syntax_kind = match token.kind {
-                TokenKind::Whitespace => SyntaxKind::Whitespace,
-                TokenKind::LineComment => SyntaxKind::LineComment,
-                TokenKind::BlockComment { terminated } => {
-                    if !terminated {
-                        res.errors.push(SyntaxError {
-                            kind: SyntaxErrorKind::UnterminatedBlockComment,
-                            token_idx: TokenIdx(res.kind.len()),
-                        });
-                    }
-                    SyntaxKind::BlockComment
-                }
-
-                TokenKind::Functor => SyntaxKind::Functor,
-                TokenKind::Variable => SyntaxKind::Variable,
-                TokenKind::Wildcard => SyntaxKind::Wildcard,
-                TokenKind::Integer => SyntaxKind::Integer,
-                TokenKind::Float => SyntaxKind::Float,
-                TokenKind::String { terminated } => {
-                    if !terminated {
-                        res.errors.push(SyntaxError {
-                            kind: SyntaxErrorKind::UnterminatedString,
-                            token_idx: TokenIdx(res.kind.len()),
-                        });
-                    }
-                    SyntaxKind::String
-                }
-
-                TokenKind::True => SyntaxKind::True,
-                TokenKind::False => SyntaxKind::False,
-
-                TokenKind::If => SyntaxKind::If,
-                TokenKind::Else => SyntaxKind::Else,
-                TokenKind::Elif => SyntaxKind::Elif,
-                TokenKind::While => SyntaxKind::While,
-                TokenKind::For => SyntaxKind::For,
-
-                TokenKind::Include => SyntaxKind::Include,
-                TokenKind::Begin => SyntaxKind::Begin,
-                TokenKind::End => SyntaxKind::End,
-
-                TokenKind::OpenParen => SyntaxKind::OpenParen,
-                TokenKind::CloseParen => SyntaxKind::CloseParen,
-                TokenKind::OpenBracket => SyntaxKind::OpenBracket,
-                TokenKind::CloseBracket => SyntaxKind::CloseBracket,
-                TokenKind::OpenBrace => SyntaxKind::OpenBrace,
-                TokenKind::CloseBrace => SyntaxKind::CloseBrace,
-
-                TokenKind::Arrow => SyntaxKind::Arrow,
-                TokenKind::ColonArrow => SyntaxKind::ColonArrow,
-                TokenKind::Define => SyntaxKind::Define,
-                TokenKind::Colon => SyntaxKind::Colon,
-                TokenKind::ColonColon => SyntaxKind::ColonColon,
-
-                TokenKind::ForkJoinAnd => SyntaxKind::ForkJoinAnd,
-                TokenKind::ForkJoinXor => SyntaxKind::ForkJoinXor,
-
-                TokenKind::BangBang => SyntaxKind::BangBang,
-                TokenKind::Bang => SyntaxKind::Bang,
-                TokenKind::Question => SyntaxKind::Question,
-                TokenKind::MinusPlus => SyntaxKind::MinusPlus,
-
-                TokenKind::Not => SyntaxKind::Not,
-                TokenKind::Tilde => SyntaxKind::Tilde,
-                TokenKind::Plus => SyntaxKind::Plus,
-                TokenKind::Minus => SyntaxKind::Minus,
-                TokenKind::Slash => SyntaxKind::Slash,
-                TokenKind::Div => SyntaxKind::Div,
-                TokenKind::Mod => SyntaxKind::Mod,
-                TokenKind::Pow => SyntaxKind::Pow,
-                TokenKind::Star => SyntaxKind::Star,
-                TokenKind::And => SyntaxKind::And,
-                TokenKind::Or => SyntaxKind::Or,
-
-                TokenKind::LtEq => SyntaxKind::LtEq,
-                TokenKind::GtEq => SyntaxKind::GtEq,
-                TokenKind::NotEqual => SyntaxKind::NotEqual,
-                TokenKind::Equal => SyntaxKind::Equal,
-                TokenKind::Decompose => SyntaxKind::Decompose,
-                TokenKind::Eq => SyntaxKind::Eq,
-                TokenKind::Lt => SyntaxKind::Lt,
-                TokenKind::Gt => SyntaxKind::Gt,
-
-                TokenKind::Semi => SyntaxKind::Semi,
-                TokenKind::Comma => SyntaxKind::Comma,
-                TokenKind::Dot => SyntaxKind::Dot,
-                TokenKind::At => SyntaxKind::At,
-
-                TokenKind::Unknown => {
-                    res.errors.push(SyntaxError {
-                        kind: SyntaxErrorKind::UnexpectedToken,
-                        token_idx: TokenIdx(res.kind.len()),
-                    });
-                    SyntaxKind::Error
-                }
-            };
+        for token in tokenize_with_options(text, options) {
+            let (syntax_kind, error) = convert(token.kind);
+            if let Some(kind) = error {
+                res.errors.push(SyntaxError {
+                    kind,
+                    token_idx: TokenIdx(res.kind.len()),
+                });
+            }
 
             res.kind.push(syntax_kind);
             res.start.push(offset);
@@ -383,14 +889,81 @@ impl LexedStr<'_> {
         res
     }
 
+    /// Tokens that were classified elsewhere, given as kinds and byte
+    /// lengths that together cover `text`.
+    pub(crate) fn from_kinds<'a>(
+        text: &'a str,
+        tokens: impl IntoIterator<Item = (SyntaxKind, usize)>,
+    ) -> LexedStr<'a> {
+        let mut res = LexedStr {
+            text,
+            kind: Vec::new(),
+            start: Vec::new(),
+            errors: Vec::new(),
+        };
+
+        let mut offset = 0;
+
+        for (kind, len) in tokens {
+            res.kind.push(kind);
+            res.start.push(offset);
+            offset += len;
+        }
+        debug_assert_eq!(offset, text.len());
+
+        res.kind.push(SyntaxKind::Eof);
+        res.start.push(offset);
+        res.start.push(offset);
+
+        res
+    }
+
     pub fn len(&self) -> usize {
         self.kind.len() - 1
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn token_range(&self, idx: TokenIdx) -> Range<usize> {
         self.start[idx.0]..self.start[idx.0 + 1]
     }
 
+    /// The token containing the byte `offset`, if it is within the text.
+    pub fn token_at_offset(&self, offset: usize) -> Option<TokenIdx> {
+        if offset >= self.start[self.len()] {
+            return None;
+        }
+        let idx = self.start[..self.len()].partition_point(|&start| start <= offset);
+        Some(TokenIdx(idx - 1))
+    }
+
+    /// Tokens overlapping the byte `range`, including tokens that only
+    /// partially overlap at either end. An empty range selects the token
+    /// containing its offset.
+    pub fn tokens_in_range(
+        &self,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = (TokenIdx, SyntaxKind, Range<usize>)> + '_ {
+        let tokens = &self.start[..self.len()];
+        let first = tokens
+            .partition_point(|&start| start <= range.start)
+            .saturating_sub(1);
+        (first..self.len())
+            .map(move |idx| {
+                (
+                    TokenIdx(idx),
+                    self.kind[idx],
+                    self.token_range(TokenIdx(idx)),
+                )
+            })
+            .skip_while(move |(_, _, token)| token.end <= range.start)
+            .take_while(move |(_, _, token)| {
+                token.start < range.end || (range.is_empty() && token.start == range.start)
+            })
+    }
+
     pub fn iter(&self) -> LexedStrIter<'_> {
         LexedStrIter {
             lexed: self,
@@ -431,3 +1004,239 @@ impl<'a> LexedStrIter<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse, test_util::Gen};
+
+    #[test]
+    fn test_stream_tokens() {
+        let options = LexerOptions::default();
+        for source in [
+            "",
+            "#!/usr/bin/env jason\n+!go(X) : X > 1 <- .print(\"hi\"); !go(X - 1).",
+            "/* unterminated",
+            "foo(\"unterminated).\n$",
+        ] {
+            let lexed = LexedStr::new(source);
+            let eager: Vec<(SyntaxKind, Range<usize>, &str)> = (0..lexed.len())
+                .map(|idx| {
+                    let range = lexed.token_range(TokenIdx(idx));
+                    (lexed.kind[idx], range.clone(), &source[range])
+                })
+                .collect();
+            let streamed: Vec<_> = stream_tokens(source, &options).collect();
+            assert_eq!(streamed, eager);
+        }
+    }
+
+    #[test]
+    fn test_prop_reconstruct() {
+        const ALPHABET: &[&str] = &[
+            "a", "X", "_", "1", "0x", "2.5", "e", ".", ",", ";", "!", "?", "+", "-", "*", "/", "<",
+            "=", ">", "\\", "|", "&", "~", "@", ":", "(", ")", "[", "]", "{", "}", "\"", "'", "#",
+            "#!", "//", "/*", "*/", " ", "\n", "\t", "\r", "ä", "δ", "€", "🦀", "\0",
+        ];
+
+        let mut gen = Gen::new(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let len = gen.below(40);
+            let source: String = (0..len).map(|_| gen.pick(ALPHABET)).collect();
+            let lexed = LexedStr::new(&source);
+            assert_eq!(reconstruct(&lexed), source);
+            #[cfg(feature = "unicode-idents")]
+            {
+                let unicode = LexedStr::with_options(
+                    &source,
+                    &LexerOptions {
+                        unicode_idents: true,
+                    },
+                );
+                assert_eq!(reconstruct(&unicode), source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_describe_error() {
+        let describe = |source: &str| -> Vec<String> {
+            let lexed = LexedStr::new(source);
+            lexed
+                .errors
+                .iter()
+                .map(|error| error.describe(&lexed))
+                .collect()
+        };
+
+        assert_eq!(describe("+!go <- x."), Vec::<String>::new());
+        assert_eq!(
+            describe("foo. /* open"),
+            ["unterminated block comment at byte 5"]
+        );
+        assert_eq!(
+            describe("+!go <- .print(\"hi)."),
+            ["unterminated string at byte 15"]
+        );
+        assert_eq!(describe("foo(a) $ bar."), ["unexpected token at byte 7"]);
+    }
+
+    #[test]
+    fn test_expected_after() {
+        let after_arrow = SyntaxKind::Arrow.expected_after();
+        for kind in [
+            SyntaxKind::Bang,
+            SyntaxKind::BangBang,
+            SyntaxKind::Question,
+            SyntaxKind::MinusPlus,
+            SyntaxKind::Functor,
+        ] {
+            assert!(after_arrow.contains(&kind), "{kind:?}");
+        }
+        assert!(!after_arrow.contains(&SyntaxKind::Dot));
+
+        for kind in [SyntaxKind::Colon, SyntaxKind::Arrow, SyntaxKind::Dot] {
+            assert!(SyntaxKind::Functor.expected_after().contains(&kind));
+            assert!(SyntaxKind::CloseParen.expected_after().contains(&kind));
+        }
+        assert_eq!(
+            SyntaxKind::Bang.expected_after(),
+            [SyntaxKind::Functor, SyntaxKind::Variable, SyntaxKind::Tilde]
+        );
+        assert_eq!(SyntaxKind::Whitespace.expected_after(), []);
+
+        // `p(_, _)`, `+!go(_) <- _ = 1` and `.print(_)`.
+        for kind in [
+            SyntaxKind::OpenParen,
+            SyntaxKind::Comma,
+            SyntaxKind::Arrow,
+            SyntaxKind::OpenBracket,
+            SyntaxKind::OpenBrace,
+        ] {
+            assert!(
+                kind.expected_after().contains(&SyntaxKind::Wildcard),
+                "{kind:?}"
+            );
+        }
+        assert_eq!(
+            SyntaxKind::Wildcard.expected_after(),
+            SyntaxKind::Variable.expected_after()
+        );
+
+        // Annotations, like `p(X)[source(a)]`.
+        assert!(SyntaxKind::Functor
+            .expected_after()
+            .contains(&SyntaxKind::OpenBracket));
+        assert!(SyntaxKind::CloseParen
+            .expected_after()
+            .contains(&SyntaxKind::OpenBracket));
+        assert!(!SyntaxKind::CloseBracket
+            .expected_after()
+            .contains(&SyntaxKind::OpenBracket));
+    }
+
+    #[test]
+    fn test_token_table() {
+        assert_eq!(
+            token_table(&LexedStr::new("+!go(X) :- \"far away\"")),
+            "Plus        0..1    \"+\"\n\
+             Bang        1..2    \"!\"\n\
+             Functor     2..4    \"go\"\n\
+             OpenParen   4..5    \"(\"\n\
+             Variable    5..6    \"X\"\n\
+             CloseParen  6..7    \")\"\n\
+             Whitespace  7..8    \" \"\n\
+             Define      8..10   \":-\"\n\
+             Whitespace  10..11  \" \"\n\
+             String      11..21  \"\\\"far away\\\"\"\n"
+        );
+        assert_eq!(token_table(&LexedStr::new("")), "");
+    }
+
+    #[test]
+    fn test_sexpr_tree() {
+        let sexpr = |source: &str| sexpr_tree(&parse(&LexedStr::new(source)).syntax());
+
+        assert_eq!(sexpr("foo."), "(Root (Belief (Literal foo) .))");
+        assert_eq!(
+            sexpr("at(home, \"big house\"). // comment\n"),
+            r#"(Root (Belief (Literal at (LiteralTerms "(" (Literal home) , "\"big house\"" ")")) .))"#
+        );
+    }
+
+    #[test]
+    fn test_token_at_offset() {
+        let source = "+!go(home) <- move.";
+        let lexed = LexedStr::new(source);
+        let token = |offset: usize| -> Option<(SyntaxKind, &str)> {
+            lexed
+                .token_at_offset(offset)
+                .map(|idx| (lexed.kind[idx.0], &source[lexed.token_range(idx)]))
+        };
+
+        assert_eq!(token(0), Some((SyntaxKind::Plus, "+")));
+        assert_eq!(token(5), Some((SyntaxKind::Functor, "home")));
+        assert_eq!(token(7), Some((SyntaxKind::Functor, "home")));
+        assert_eq!(token(10), Some((SyntaxKind::Whitespace, " ")));
+        assert_eq!(token(source.len() - 1), Some((SyntaxKind::Dot, ".")));
+        assert_eq!(token(source.len()), None);
+        assert_eq!(token(1000), None);
+        assert_eq!(LexedStr::new("").token_at_offset(0).map(|idx| idx.0), None);
+    }
+
+    #[test]
+    fn test_tokens_in_range() {
+        let source = "+!go(home, X) <- move.";
+        let lexed = LexedStr::new(source);
+        let select = |range: Range<usize>| -> Vec<(usize, SyntaxKind, &str)> {
+            lexed
+                .tokens_in_range(range)
+                .map(|(idx, kind, range)| (idx.0, kind, &source[range]))
+                .collect()
+        };
+
+        assert_eq!(
+            select(3..6),
+            [
+                (2, SyntaxKind::Functor, "go"),
+                (3, SyntaxKind::OpenParen, "("),
+                (4, SyntaxKind::Functor, "home"),
+            ]
+        );
+        assert_eq!(
+            select(6..11),
+            [
+                (4, SyntaxKind::Functor, "home"),
+                (5, SyntaxKind::Comma, ","),
+                (6, SyntaxKind::Whitespace, " "),
+            ]
+        );
+        assert_eq!(select(4..5), [(3, SyntaxKind::OpenParen, "(")]);
+        assert_eq!(select(7..7), [(4, SyntaxKind::Functor, "home")]);
+        assert_eq!(select(0..1), [(0, SyntaxKind::Plus, "+")]);
+        assert_eq!(
+            select(17..100),
+            [
+                (12, SyntaxKind::Functor, "move"),
+                (13, SyntaxKind::Dot, "."),
+            ]
+        );
+        assert!(select(22..22).is_empty());
+        assert!(select(50..60).is_empty());
+    }
+
+    #[test]
+    fn test_node_classification() {
+        assert!(SyntaxKind::Comparison.is_expression());
+        assert!(!SyntaxKind::Comparison.is_structural());
+
+        assert!(SyntaxKind::Plan.is_structural());
+        assert!(!SyntaxKind::Plan.is_expression());
+
+        assert!(SyntaxKind::Literal.is_expression());
+        assert!(!SyntaxKind::Literal.is_structural());
+
+        assert!(!SyntaxKind::Functor.is_expression());
+        assert!(!SyntaxKind::Functor.is_structural());
+    }
+}
@@ -0,0 +1,20 @@
+/// Deterministic pseudo-random numbers for property tests (xorshift64*).
+pub(crate) struct Gen(u64);
+
+impl Gen {
+    /// The seed must not be zero.
+    pub(crate) fn new(seed: u64) -> Gen {
+        Gen(seed)
+    }
+
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % n
+    }
+
+    pub(crate) fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
@@ -0,0 +1,91 @@
+//! Golden tests for the parser. Every `tests/data/*.asl` file is parsed and
+//! its tree and errors are compared against the `.tree` file next to it.
+//! Run with `UPDATE_GOLDEN=1` to regenerate the expectations.
+
+use std::{env, fmt::Write as _, fs, path::Path};
+
+use pheres::{
+    parser::parse,
+    syntax::{debug_tree, LexedStr},
+};
+
+fn render(source: &str) -> String {
+    let lexed = LexedStr::new(source);
+    let parsed = parse(&lexed);
+
+    let mut out = debug_tree(&parsed.syntax());
+    for error in &lexed.errors {
+        let range = lexed.token_range(error.token_idx);
+        writeln!(out, "error {:?}: {}", range, error.kind).unwrap();
+    }
+    for error in &parsed.errors {
+        let range = lexed.token_range(error.token_idx);
+        writeln!(out, "error {:?}: {}", range, error).unwrap();
+    }
+    if parsed.unexpected_eof {
+        writeln!(out, "error: unexpected end of file").unwrap();
+    }
+    out
+}
+
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::new();
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => writeln!(out, "  {}", e).unwrap(),
+            (e, a) => {
+                if let Some(e) = e {
+                    writeln!(out, "- {}", e).unwrap();
+                }
+                if let Some(a) = a {
+                    writeln!(out, "+ {}", a).unwrap();
+                }
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn test_golden() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asl"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no inputs in {}", dir.display());
+
+    let mut failures = Vec::new();
+
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        let actual = render(&source);
+        let golden = path.with_extension("tree");
+
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if expected != actual {
+            failures.push(format!(
+                "{}:\n{}",
+                golden.display(),
+                diff(&expected, &actual)
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden trees differ (rerun with UPDATE_GOLDEN=1 to accept):\n\n{}",
+        failures.join("\n")
+    );
+}